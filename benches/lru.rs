@@ -0,0 +1,165 @@
+use std::{
+    hash::{BuildHasherDefault, Hasher},
+    hint::black_box,
+    time::{Duration, Instant},
+};
+
+use lru_cache::{
+    policy::{Fifo, Lfu, Lru, SecondChance, Slru, TinyLfu},
+    ArrayLruCache, Cache, EvictionPolicy, LruCache,
+};
+
+const CAPACITY: u64 = 1_000;
+const OPS: u64 = 2_000_000;
+
+struct XorShift(u64);
+
+impl XorShift {
+    fn next(&mut self) -> u64 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        self.0
+    }
+}
+
+/// Samples ranks `0..n` with probability proportional to `1 / (rank + 1)^s`.
+struct Zipf {
+    cdf: Vec<f64>,
+}
+
+impl Zipf {
+    fn new(n: u64, s: f64) -> Self {
+        let mut sum = 0.0;
+        let mut cdf: Vec<f64> = (1..=n)
+            .map(|rank| {
+                sum += 1.0 / (rank as f64).powf(s);
+                sum
+            })
+            .collect();
+
+        for p in &mut cdf {
+            *p /= sum;
+        }
+
+        Self { cdf }
+    }
+
+    fn sample(&self, rng: &mut XorShift) -> u64 {
+        let u = (rng.next() >> 11) as f64 / (1u64 << 53) as f64;
+        self.cdf.partition_point(|&p| p < u) as u64
+    }
+}
+
+/// The multiply-rotate hash used by rustc, much cheaper than SipHash for
+/// integer keys.
+#[derive(Default)]
+struct FxHasher(u64);
+
+impl Hasher for FxHasher {
+    fn write(&mut self, bytes: &[u8]) {
+        for &byte in bytes {
+            self.write_u64(byte.into());
+        }
+    }
+
+    fn write_u64(&mut self, n: u64) {
+        self.0 = (self.0.rotate_left(5) ^ n).wrapping_mul(0x517c_c1b7_2722_0a95);
+    }
+
+    fn finish(&self) -> u64 {
+        self.0
+    }
+}
+
+fn run<P: EvictionPolicy>(policy: P, key_space: u64) -> (Duration, u64) {
+    let cache = LruCache::with_policy(CAPACITY as usize, policy);
+    run_trace(cache, |rng| rng.next() % key_space)
+}
+
+fn run_fx(key_space: u64) -> (Duration, u64) {
+    let cache = LruCache::with_capacity_and_hasher(
+        CAPACITY as usize,
+        BuildHasherDefault::<FxHasher>::default(),
+    );
+    run_trace(cache, |rng| rng.next() % key_space)
+}
+
+fn run_zipf<P: EvictionPolicy>(policy: P, zipf: &Zipf) -> (Duration, u64) {
+    let cache = LruCache::with_policy(CAPACITY as usize, policy);
+    run_trace(cache, |rng| zipf.sample(rng))
+}
+
+fn run_array(key_space: u64) -> (Duration, u64) {
+    let cache = ArrayLruCache::<u64, u64, { CAPACITY as usize }>::new();
+    run_trace(cache, |rng| rng.next() % key_space)
+}
+
+fn run_trace<C: Cache<u64, u64>>(
+    mut cache: C,
+    mut next_key: impl FnMut(&mut XorShift) -> u64,
+) -> (Duration, u64) {
+    let mut rng = XorShift(0x2545_f491_4f6c_dd1d);
+    let mut hits = 0;
+
+    for k in 0..CAPACITY {
+        cache.insert(k, k);
+    }
+
+    let start = Instant::now();
+
+    for _ in 0..OPS {
+        let k = next_key(&mut rng);
+
+        if black_box(cache.get(&k)).is_some() {
+            hits += 1;
+        } else {
+            cache.insert(k, k);
+        }
+    }
+
+    (start.elapsed(), hits)
+}
+
+fn report(name: &str, (elapsed, hits): (Duration, u64)) {
+    println!(
+        "{name:<18} {:>8.1} ns/op {:>6.2}% hits",
+        elapsed.as_nanos() as f64 / OPS as f64,
+        hits as f64 * 100.0 / OPS as f64,
+    );
+}
+
+fn main() {
+    report("hit-heavy", run(Lru, CAPACITY));
+    report("hit-heavy fxhash", run_fx(CAPACITY));
+    report("hit-heavy array", run_array(CAPACITY));
+    report("miss-heavy", run(Lru, CAPACITY * 10));
+    report("miss-heavy fxhash", run_fx(CAPACITY * 10));
+    report("miss-heavy array", run_array(CAPACITY * 10));
+    report("miss-heavy fifo", run(Fifo::new(), CAPACITY * 10));
+    report("miss-heavy lfu", run(Lfu::new(), CAPACITY * 10));
+    report("miss-heavy clock", run(SecondChance::new(), CAPACITY * 10));
+    report(
+        "miss-heavy slru",
+        run(Slru::with_ratio(CAPACITY as usize, 0.8), CAPACITY * 10),
+    );
+    report(
+        "miss-heavy tinylfu",
+        run(TinyLfu::new(CAPACITY as usize), CAPACITY * 10),
+    );
+
+    for s in [0.7, 0.9, 1.1] {
+        let zipf = Zipf::new(CAPACITY * 100, s);
+
+        println!("zipf s={s}");
+        report("  lru", run_zipf(Lru, &zipf));
+        report(
+            "  slru",
+            run_zipf(Slru::with_ratio(CAPACITY as usize, 0.8), &zipf),
+        );
+        report(
+            "  tinylfu",
+            run_zipf(TinyLfu::new(CAPACITY as usize), &zipf),
+        );
+    }
+}
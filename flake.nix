@@ -17,9 +17,9 @@
         devShell = pkgs.mkShell {
           buildInputs = with pkgs; [
             bacon
-            (rust-bin.selectLatestNightlyWith (toolchain: toolchain.default.override {
+            (rust-bin.stable.latest.default.override {
               extensions = [ "rust-analyzer" "rust-src" ];
-            }))
+            })
           ];
         };
       }
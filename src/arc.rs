@@ -0,0 +1,518 @@
+use core::{borrow::Borrow, hash::Hash, mem};
+
+use hashbrown::HashTable;
+
+use crate::{make_hash, Cache, DefaultHashBuilder, Graph, NodeId};
+
+/// Where a key lives: in one of the two resident lists, or as a ghost in one
+/// of the two history lists.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Location {
+    T1(NodeId),
+    T2(NodeId),
+    B1(NodeId),
+    B2(NodeId),
+}
+
+#[derive(Debug)]
+struct Lists<K, V> {
+    t1: Graph<(K, V)>,
+    t2: Graph<(K, V)>,
+    b1: Graph<K>,
+    b2: Graph<K>,
+    t1_len: usize,
+    t2_len: usize,
+    b1_len: usize,
+    b2_len: usize,
+}
+
+impl<K, V> Lists<K, V> {
+    fn key(&self, location: Location) -> &K {
+        match location {
+            Location::T1(node_id) => &self.t1.node(node_id).unwrap().element.0,
+            Location::T2(node_id) => &self.t2.node(node_id).unwrap().element.0,
+            Location::B1(node_id) => &self.b1.node(node_id).unwrap().element,
+            Location::B2(node_id) => &self.b2.node(node_id).unwrap().element,
+        }
+    }
+}
+
+/// An Adaptive Replacement Cache.
+///
+/// Resident entries are split between T1, holding keys seen once recently,
+/// and T2, holding keys seen at least twice. Keys evicted from either list are
+/// remembered, without their values, in the ghost lists B1 and B2. Inserting
+/// a key that is still remembered in B1 means T1 was too small, so the target
+/// size `p` of T1 grows; one remembered in B2 shrinks it. This lets the cache
+/// shift between favouring recency and frequency to suit the workload.
+///
+/// See Megiddo and Modha, "ARC: A Self-Tuning, Low Overhead Replacement
+/// Cache" (FAST 2003).
+#[derive(Debug)]
+pub struct ArcCache<K, V> {
+    locations: HashTable<Location>,
+    lists: Lists<K, V>,
+    hash_builder: DefaultHashBuilder,
+    capacity: usize,
+    p: usize,
+}
+
+impl<K, V> ArcCache<K, V>
+where
+    K: Eq + Hash,
+{
+    /// Creates a cache holding at most `capacity` entries, and remembering as
+    /// many evicted keys.
+    ///
+    /// Only the index is allocated up front, for the resident and remembered
+    /// keys, as how they split between the lists depends on the workload.
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            locations: HashTable::with_capacity(capacity.saturating_mul(2)),
+            lists: Lists {
+                t1: Graph::default(),
+                t2: Graph::default(),
+                b1: Graph::default(),
+                b2: Graph::default(),
+                t1_len: 0,
+                t2_len: 0,
+                b1_len: 0,
+                b2_len: 0,
+            },
+            hash_builder: DefaultHashBuilder::default(),
+            capacity,
+            p: 0,
+        }
+    }
+
+    /// Returns the number of resident entries. Ghost keys are not counted.
+    pub fn len(&self) -> usize {
+        self.lists.t1_len + self.lists.t2_len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    pub fn get<Q>(&mut self, k: &Q) -> Option<&V>
+    where
+        K: Borrow<Q>,
+        Q: ?Sized + Hash + Eq,
+    {
+        let hash = make_hash(&self.hash_builder, k);
+
+        match self.find(hash, k)? {
+            location @ (Location::T1(_) | Location::T2(_)) => {
+                let node_id = self.promote(hash, location);
+                Some(&self.lists.t2.node(node_id).unwrap().element.1)
+            }
+            Location::B1(_) | Location::B2(_) => None,
+        }
+    }
+
+    /// Returns the value for a key without counting it as a hit.
+    pub fn peek<Q>(&self, k: &Q) -> Option<&V>
+    where
+        K: Borrow<Q>,
+        Q: ?Sized + Hash + Eq,
+    {
+        match self.find(make_hash(&self.hash_builder, k), k)? {
+            Location::T1(node_id) => Some(&self.lists.t1.node(node_id).unwrap().element.1),
+            Location::T2(node_id) => Some(&self.lists.t2.node(node_id).unwrap().element.1),
+            Location::B1(_) | Location::B2(_) => None,
+        }
+    }
+
+    pub fn contains_key<Q>(&self, k: &Q) -> bool
+    where
+        K: Borrow<Q>,
+        Q: ?Sized + Hash + Eq,
+    {
+        self.peek(k).is_some()
+    }
+
+    /// Removes a key, returning its value if it was resident. A ghost entry
+    /// for the key is forgotten as well.
+    pub fn remove<Q>(&mut self, k: &Q) -> Option<V>
+    where
+        K: Borrow<Q>,
+        Q: ?Sized + Hash + Eq,
+    {
+        let hash = make_hash(&self.hash_builder, k);
+        let lists = &self.lists;
+        let entry = self
+            .locations
+            .find_entry(hash, |&location| lists.key(location).borrow() == k)
+            .ok()?;
+        let (location, _) = entry.remove();
+
+        let lists = &mut self.lists;
+
+        match location {
+            Location::T1(node_id) => {
+                lists.t1_len -= 1;
+                lists.t1.remove(node_id).map(|(_k, v)| v)
+            }
+            Location::T2(node_id) => {
+                lists.t2_len -= 1;
+                lists.t2.remove(node_id).map(|(_k, v)| v)
+            }
+            Location::B1(node_id) => {
+                lists.b1_len -= 1;
+                lists.b1.remove(node_id);
+                None
+            }
+            Location::B2(node_id) => {
+                lists.b2_len -= 1;
+                lists.b2.remove(node_id);
+                None
+            }
+        }
+    }
+
+    /// Inserts a key-value pair, returning the previous value if the key was
+    /// resident.
+    ///
+    /// A resident key counts as a hit and moves to T2. A key remembered in a
+    /// ghost list adapts `p` and goes straight to T2. Any other key enters T1.
+    pub fn insert(&mut self, k: K, v: V) -> Option<V> {
+        if self.capacity == 0 {
+            return None;
+        }
+
+        let hash = make_hash(&self.hash_builder, &k);
+
+        match self.find(hash, &k) {
+            Some(location @ (Location::T1(_) | Location::T2(_))) => {
+                let node_id = self.promote(hash, location);
+                let (_k, old_v) = &mut self.lists.t2.node_mut(node_id).unwrap().element;
+                Some(mem::replace(old_v, v))
+            }
+            Some(Location::B1(node_id)) => {
+                let delta = (self.lists.b2_len / self.lists.b1_len).max(1);
+                self.p = (self.p + delta).min(self.capacity);
+                self.make_room(false);
+
+                self.lists.b1.remove(node_id);
+                self.lists.b1_len -= 1;
+                self.push_t2(hash, Location::B1(node_id), k, v);
+                None
+            }
+            Some(Location::B2(node_id)) => {
+                let delta = (self.lists.b1_len / self.lists.b2_len).max(1);
+                self.p = self.p.saturating_sub(delta);
+                self.make_room(true);
+
+                self.lists.b2.remove(node_id);
+                self.lists.b2_len -= 1;
+                self.push_t2(hash, Location::B2(node_id), k, v);
+                None
+            }
+            None => {
+                let lists = &self.lists;
+                let total = lists.t1_len + lists.t2_len + lists.b1_len + lists.b2_len;
+
+                if lists.t1_len + lists.b1_len >= self.capacity {
+                    if lists.t1_len < self.capacity {
+                        self.forget_lru(false);
+                        self.make_room(false);
+                    } else {
+                        self.drop_t1_lru();
+                    }
+                } else if total >= self.capacity {
+                    if total >= 2 * self.capacity {
+                        self.forget_lru(true);
+                    }
+
+                    self.make_room(false);
+                }
+
+                let node_id = self.lists.t1.push_back((k, v));
+                self.lists.t1_len += 1;
+
+                let (lists, hash_builder) = (&self.lists, &self.hash_builder);
+                self.locations
+                    .insert_unique(hash, Location::T1(node_id), |&location| {
+                        make_hash(hash_builder, lists.key(location))
+                    });
+
+                None
+            }
+        }
+    }
+
+    fn find<Q>(&self, hash: u64, k: &Q) -> Option<Location>
+    where
+        K: Borrow<Q>,
+        Q: ?Sized + Eq,
+    {
+        let lists = &self.lists;
+        let location = self
+            .locations
+            .find(hash, |&location| lists.key(location).borrow() == k)?;
+
+        Some(*location)
+    }
+
+    fn relocate(&mut self, hash: u64, from: Location, to: Location) {
+        if let Some(location) = self.locations.find_mut(hash, |&location| location == from) {
+            *location = to;
+        }
+    }
+
+    /// Moves a resident entry to the most recently used end of T2, returning
+    /// its node there.
+    fn promote(&mut self, hash: u64, location: Location) -> NodeId {
+        match location {
+            Location::T1(node_id) => {
+                let element = self.lists.t1.remove(node_id).unwrap();
+                self.lists.t1_len -= 1;
+
+                let t2_id = self.lists.t2.push_back(element);
+                self.lists.t2_len += 1;
+                self.relocate(hash, location, Location::T2(t2_id));
+                t2_id
+            }
+            Location::T2(node_id) => {
+                self.lists.t2.move_to_back(node_id);
+                node_id
+            }
+            Location::B1(_) | Location::B2(_) => unreachable!(),
+        }
+    }
+
+    fn push_t2(&mut self, hash: u64, from: Location, k: K, v: V) {
+        let node_id = self.lists.t2.push_back((k, v));
+        self.lists.t2_len += 1;
+        self.relocate(hash, from, Location::T2(node_id));
+    }
+
+    /// The REPLACE step of ARC: if the cache is full, demotes the least
+    /// recently used entry of T1 or T2 to its ghost list, depending on how T1
+    /// compares to its target size.
+    fn make_room(&mut self, hit_in_b2: bool) {
+        let lists = &self.lists;
+
+        if lists.t1_len + lists.t2_len < self.capacity {
+            return;
+        }
+
+        let from_t1 = lists.t1_len > 0
+            && (lists.t1_len > self.p
+                || (hit_in_b2 && lists.t1_len == self.p)
+                || lists.t2_len == 0);
+
+        if from_t1 {
+            let head_id = self.lists.t1.head_id.unwrap();
+            let (k, _v) = self.lists.t1.pop_front().unwrap();
+            self.lists.t1_len -= 1;
+
+            let hash = make_hash(&self.hash_builder, &k);
+            let node_id = self.lists.b1.push_back(k);
+            self.lists.b1_len += 1;
+            self.relocate(hash, Location::T1(head_id), Location::B1(node_id));
+        } else {
+            let head_id = self.lists.t2.head_id.unwrap();
+            let (k, _v) = self.lists.t2.pop_front().unwrap();
+            self.lists.t2_len -= 1;
+
+            let hash = make_hash(&self.hash_builder, &k);
+            let node_id = self.lists.b2.push_back(k);
+            self.lists.b2_len += 1;
+            self.relocate(hash, Location::T2(head_id), Location::B2(node_id));
+        }
+    }
+
+    /// Drops the least recently used ghost key of B1, or of B2 if `b2`.
+    fn forget_lru(&mut self, b2: bool) {
+        let (list, len, location) = if b2 {
+            (
+                &mut self.lists.b2,
+                &mut self.lists.b2_len,
+                Location::B2 as fn(_) -> _,
+            )
+        } else {
+            (
+                &mut self.lists.b1,
+                &mut self.lists.b1_len,
+                Location::B1 as fn(_) -> _,
+            )
+        };
+
+        let Some(head_id) = list.head_id else {
+            return;
+        };
+
+        let k = list.pop_front().unwrap();
+        *len -= 1;
+        self.unindex(make_hash(&self.hash_builder, &k), location(head_id));
+    }
+
+    /// Drops the least recently used entry of T1 without remembering it.
+    fn drop_t1_lru(&mut self) {
+        let Some(head_id) = self.lists.t1.head_id else {
+            return;
+        };
+
+        let (k, _v) = self.lists.t1.pop_front().unwrap();
+        self.lists.t1_len -= 1;
+        self.unindex(make_hash(&self.hash_builder, &k), Location::T1(head_id));
+    }
+
+    fn unindex(&mut self, hash: u64, location: Location) {
+        if let Ok(entry) = self.locations.find_entry(hash, |&l| l == location) {
+            entry.remove();
+        }
+    }
+}
+
+impl<K, V> Cache<K, V> for ArcCache<K, V>
+where
+    K: Eq + Hash,
+{
+    fn get(&mut self, k: &K) -> Option<&V> {
+        ArcCache::get(self, k)
+    }
+
+    fn insert(&mut self, k: K, v: V) -> Option<V> {
+        ArcCache::insert(self, k, v)
+    }
+
+    fn remove(&mut self, k: &K) -> Option<V> {
+        ArcCache::remove(self, k)
+    }
+
+    fn len(&self) -> usize {
+        ArcCache::len(self)
+    }
+
+    fn capacity(&self) -> usize {
+        ArcCache::capacity(self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::vec::Vec;
+
+    use super::*;
+    use crate::tests::xorshift;
+
+    fn keys<K: Clone, V>(graph: &Graph<(K, V)>) -> Vec<K> {
+        let mut keys = Vec::new();
+        let mut node_id = graph.head_id;
+
+        while let Some(id) = node_id {
+            let node = graph.node(id).unwrap();
+            keys.push(node.element.0.clone());
+            node_id = node.next_id;
+        }
+
+        keys
+    }
+
+    #[test]
+    fn test_arc_basics() {
+        let mut cache = ArcCache::new(2);
+
+        assert_eq!(cache.insert(1, "a"), None);
+        assert_eq!(cache.insert(2, "b"), None);
+        assert_eq!(cache.insert(1, "c"), Some("a"));
+        assert_eq!(cache.get(&1), Some(&"c"));
+        assert_eq!(cache.len(), 2);
+
+        cache.insert(3, "d");
+        assert_eq!(cache.len(), 2);
+        assert_eq!(cache.peek(&2), None);
+        assert!(cache.contains_key(&1));
+
+        assert_eq!(cache.remove(&3), Some("d"));
+        assert_eq!(cache.remove(&2), None);
+        assert_eq!(cache.len(), 1);
+    }
+
+    #[test]
+    fn test_arc_frequent_keys_survive_scan() {
+        let mut cache = ArcCache::new(4);
+
+        for k in 0..2 {
+            cache.insert(k, k);
+            cache.get(&k);
+        }
+
+        for k in 100..200 {
+            cache.insert(k, k);
+        }
+
+        assert!(cache.contains_key(&0) && cache.contains_key(&1));
+        assert_eq!(keys(&cache.lists.t2), [0, 1]);
+        assert_eq!(cache.len(), 4);
+    }
+
+    #[test]
+    fn test_arc_adapts_target() {
+        let mut cache = ArcCache::new(2);
+
+        cache.insert(1, 1);
+        cache.insert(2, 2);
+        cache.get(&2);
+        cache.insert(3, 3);
+        assert_eq!(keys(&cache.lists.t1), [3]);
+        assert_eq!(cache.lists.b1_len, 1);
+        assert_eq!(cache.p, 0);
+
+        cache.insert(1, 10);
+        assert_eq!(cache.p, 1);
+        assert_eq!(keys(&cache.lists.t2), [1]);
+        assert_eq!(cache.lists.b2_len, 1);
+        assert_eq!(cache.peek(&1), Some(&10));
+
+        cache.insert(2, 20);
+        assert_eq!(cache.p, 0);
+        assert_eq!(keys(&cache.lists.t2), [1, 2]);
+        assert_eq!(cache.lists.b1.front(), Some(&3));
+        assert_eq!(cache.lists.b1_len, 1);
+        assert_eq!(cache.lists.b2_len, 0);
+    }
+
+    #[test]
+    fn test_arc_invariants() {
+        let mut cache = ArcCache::new(8);
+
+        for seed in xorshift(0x9e37_79b9).take(10_000) {
+            let k = seed % 40;
+
+            match seed % 4 {
+                0 => {
+                    cache.remove(&k);
+                }
+                1 => {
+                    cache.get(&k);
+                }
+                _ => {
+                    cache.insert(k, k);
+                }
+            }
+
+            let lists = &cache.lists;
+            let ghosts = lists.b1_len + lists.b2_len;
+            assert!(cache.len() <= 8);
+            assert!(lists.t1_len + lists.b1_len <= 8);
+            assert!(cache.len() + ghosts <= 16);
+            assert!(cache.p <= 8);
+            assert_eq!(cache.locations.len(), cache.len() + ghosts);
+        }
+    }
+
+    #[test]
+    fn test_zero_capacity() {
+        let mut cache = ArcCache::new(0);
+
+        assert_eq!(cache.insert(1, 1), None);
+        assert!(cache.is_empty());
+    }
+}
@@ -0,0 +1,515 @@
+use core::{
+    borrow::Borrow,
+    fmt,
+    hash::{BuildHasher, Hash},
+    mem,
+};
+
+use crate::{make_hash, Cache, DefaultHashBuilder};
+
+/// The integer type an [`ArrayLruCache`] links its nodes with.
+///
+/// Implemented for `u16` and `u32`. A cache's capacity must stay below the
+/// index type's maximum value, which marks the end of a list.
+pub trait ArrayIndex: Copy + Eq + sealed::Sealed {
+    #[doc(hidden)]
+    const NONE: Self;
+    #[doc(hidden)]
+    const MAX_CAPACITY: usize;
+    #[doc(hidden)]
+    fn new(index: usize) -> Self;
+    #[doc(hidden)]
+    fn get(self) -> Option<usize>;
+}
+
+mod sealed {
+    pub trait Sealed {}
+
+    impl Sealed for u16 {}
+    impl Sealed for u32 {}
+}
+
+macro_rules! impl_array_index {
+    ($($t:ty),*) => {$(
+        impl ArrayIndex for $t {
+            const NONE: Self = <$t>::MAX;
+            const MAX_CAPACITY: usize = <$t>::MAX as usize;
+
+            fn new(index: usize) -> Self {
+                index as $t
+            }
+
+            fn get(self) -> Option<usize> {
+                (self != Self::NONE).then_some(self as usize)
+            }
+        }
+    )*};
+}
+
+impl_array_index!(u16, u32);
+
+struct Node<K, V, I> {
+    entry: Option<(K, V)>,
+    prev: I,
+    next: I,
+    /// The next node in the same bucket.
+    chain: I,
+}
+
+impl<K, V, I: ArrayIndex> Node<K, V, I> {
+    const EMPTY: Self = Self {
+        entry: None,
+        prev: I::NONE,
+        next: I::NONE,
+        chain: I::NONE,
+    };
+}
+
+/// An LRU cache of at most `N` entries that never allocates.
+///
+/// Entries live in an inline array of nodes doubly linked from least to most
+/// recently used, like [`LruCache`](crate::LruCache)'s, and are indexed by a
+/// separately chained hash table of `N` buckets, also inline. Links are `I`
+/// sized, `u32` by default, which halves their footprint over `usize` on
+/// 64-bit targets; `u16` suffices below 65535 entries.
+///
+/// [`with_hasher`](Self::with_hasher) is a `const fn`, so a cache can be built
+/// in a `const` or `static` given a hasher that can be too.
+pub struct ArrayLruCache<K, V, const N: usize, I = u32, S = DefaultHashBuilder> {
+    nodes: [Node<K, V, I>; N],
+    buckets: [I; N],
+    hash_builder: S,
+    head: I,
+    tail: I,
+    /// The most recently freed node, heading a list linked through `next`.
+    free: I,
+    /// How many nodes have ever been used; those above are all free.
+    used: usize,
+    len: usize,
+}
+
+impl<K, V, const N: usize, I, S> ArrayLruCache<K, V, N, I, S>
+where
+    K: Eq + Hash,
+    I: ArrayIndex,
+    S: BuildHasher + Default,
+{
+    pub fn new() -> Self {
+        Self::with_hasher(S::default())
+    }
+}
+
+impl<K, V, const N: usize, I, S> Default for ArrayLruCache<K, V, N, I, S>
+where
+    K: Eq + Hash,
+    I: ArrayIndex,
+    S: BuildHasher + Default,
+{
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<K, V, const N: usize, I, S> ArrayLruCache<K, V, N, I, S>
+where
+    K: Eq + Hash,
+    I: ArrayIndex,
+    S: BuildHasher,
+{
+    /// Creates an empty cache hashing keys with `hash_builder`.
+    ///
+    /// # Panics
+    ///
+    /// Panics, at compile time in a `const` context, if `N` does not fit the
+    /// index type.
+    pub const fn with_hasher(hash_builder: S) -> Self {
+        assert!(N < I::MAX_CAPACITY, "capacity does not fit the index type");
+
+        Self {
+            nodes: [Node::EMPTY; N],
+            buckets: [I::NONE; N],
+            hash_builder,
+            head: I::NONE,
+            tail: I::NONE,
+            free: I::NONE,
+            used: 0,
+            len: 0,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub fn capacity(&self) -> usize {
+        N
+    }
+
+    pub fn get<Q>(&mut self, k: &Q) -> Option<&V>
+    where
+        K: Borrow<Q>,
+        Q: ?Sized + Hash + Eq,
+    {
+        let index = self.find(k)?;
+        self.move_to_back(index);
+        Some(&self.entry(index).1)
+    }
+
+    pub fn get_mut<Q>(&mut self, k: &Q) -> Option<&mut V>
+    where
+        K: Borrow<Q>,
+        Q: ?Sized + Hash + Eq,
+    {
+        let index = self.find(k)?;
+        self.move_to_back(index);
+        Some(&mut self.entry_mut(index).1)
+    }
+
+    /// Returns the value for a key without promoting it.
+    pub fn peek<Q>(&self, k: &Q) -> Option<&V>
+    where
+        K: Borrow<Q>,
+        Q: ?Sized + Hash + Eq,
+    {
+        let index = self.find(k)?;
+        Some(&self.entry(index).1)
+    }
+
+    pub fn contains_key<Q>(&self, k: &Q) -> bool
+    where
+        K: Borrow<Q>,
+        Q: ?Sized + Hash + Eq,
+    {
+        self.find(k).is_some()
+    }
+
+    /// Returns the least recently used entry, the next one to be evicted.
+    pub fn peek_lru(&self) -> Option<(&K, &V)> {
+        let (k, v) = self.entry(self.head.get()?);
+        Some((k, v))
+    }
+
+    /// Inserts a key-value pair, promoting it to most recently used and
+    /// evicting the least recently used entry if the cache is full. Returns
+    /// the old value if the key was already present.
+    ///
+    /// A cache with `N == 0` drops the pair right away.
+    pub fn insert(&mut self, k: K, v: V) -> Option<V> {
+        match self.find(&k) {
+            Some(index) => {
+                self.move_to_back(index);
+                Some(mem::replace(&mut self.entry_mut(index).1, v))
+            }
+            None => {
+                let _ = self.push_new(k, v);
+                None
+            }
+        }
+    }
+
+    /// Inserts a key-value pair like [`insert`](Self::insert), but returns
+    /// the pair it displaced: the given key with the old value, the evicted
+    /// entry, or the pair itself if `N == 0`.
+    pub fn push(&mut self, k: K, v: V) -> Option<(K, V)> {
+        match self.find(&k) {
+            Some(index) => {
+                self.move_to_back(index);
+                let old_v = mem::replace(&mut self.entry_mut(index).1, v);
+                Some((k, old_v))
+            }
+            None => self.push_new(k, v),
+        }
+    }
+
+    pub fn remove<Q>(&mut self, k: &Q) -> Option<V>
+    where
+        K: Borrow<Q>,
+        Q: ?Sized + Hash + Eq,
+    {
+        let index = self.find(k)?;
+        Some(self.remove_node(index).1)
+    }
+
+    pub fn clear(&mut self) {
+        while let Some(head) = self.head.get() {
+            self.remove_node(head);
+        }
+    }
+
+    fn bucket<Q>(&self, k: &Q) -> usize
+    where
+        Q: ?Sized + Hash,
+    {
+        (make_hash(&self.hash_builder, k) % N as u64) as usize
+    }
+
+    fn find<Q>(&self, k: &Q) -> Option<usize>
+    where
+        K: Borrow<Q>,
+        Q: ?Sized + Hash + Eq,
+    {
+        if N == 0 {
+            return None;
+        }
+
+        let mut index = self.buckets[self.bucket(k)].get();
+
+        while let Some(i) = index {
+            if self.entry(i).0.borrow() == k {
+                return Some(i);
+            }
+
+            index = self.nodes[i].chain.get();
+        }
+
+        None
+    }
+
+    fn entry(&self, index: usize) -> &(K, V) {
+        self.nodes[index].entry.as_ref().unwrap()
+    }
+
+    fn entry_mut(&mut self, index: usize) -> &mut (K, V) {
+        self.nodes[index].entry.as_mut().unwrap()
+    }
+
+    fn push_new(&mut self, k: K, v: V) -> Option<(K, V)> {
+        if N == 0 {
+            return Some((k, v));
+        }
+
+        let evicted = match self.head.get() {
+            Some(head) if self.len == N => Some(self.remove_node(head)),
+            _ => None,
+        };
+
+        let index = match self.free.get() {
+            Some(index) => {
+                self.free = self.nodes[index].next;
+                index
+            }
+            None => {
+                self.used += 1;
+                self.used - 1
+            }
+        };
+
+        let bucket = self.bucket(&k);
+        let node = &mut self.nodes[index];
+        node.entry = Some((k, v));
+        node.chain = mem::replace(&mut self.buckets[bucket], I::new(index));
+        self.link_back(index);
+        self.len += 1;
+
+        evicted
+    }
+
+    fn remove_node(&mut self, index: usize) -> (K, V) {
+        self.unchain(index);
+        self.unlink(index);
+
+        let node = &mut self.nodes[index];
+        let entry = node.entry.take().unwrap();
+        node.next = mem::replace(&mut self.free, I::new(index));
+        self.len -= 1;
+
+        entry
+    }
+
+    /// Removes a node from its bucket's chain.
+    fn unchain(&mut self, index: usize) {
+        let bucket = self.bucket(&self.entry(index).0);
+        let chain = self.nodes[index].chain;
+
+        if self.buckets[bucket].get() == Some(index) {
+            self.buckets[bucket] = chain;
+            return;
+        }
+
+        let mut prev = self.buckets[bucket].get().unwrap();
+
+        while self.nodes[prev].chain.get() != Some(index) {
+            prev = self.nodes[prev].chain.get().unwrap();
+        }
+
+        self.nodes[prev].chain = chain;
+    }
+
+    fn unlink(&mut self, index: usize) {
+        let Node { prev, next, .. } = self.nodes[index];
+
+        match prev.get() {
+            Some(prev) => self.nodes[prev].next = next,
+            None => self.head = next,
+        }
+
+        match next.get() {
+            Some(next) => self.nodes[next].prev = prev,
+            None => self.tail = prev,
+        }
+    }
+
+    fn link_back(&mut self, index: usize) {
+        let tail = mem::replace(&mut self.tail, I::new(index));
+        self.nodes[index].prev = tail;
+        self.nodes[index].next = I::NONE;
+
+        match tail.get() {
+            Some(tail) => self.nodes[tail].next = I::new(index),
+            None => self.head = I::new(index),
+        }
+    }
+
+    fn move_to_back(&mut self, index: usize) {
+        if self.tail.get() != Some(index) {
+            self.unlink(index);
+            self.link_back(index);
+        }
+    }
+}
+
+impl<K, V, const N: usize, I, S> Cache<K, V> for ArrayLruCache<K, V, N, I, S>
+where
+    K: Eq + Hash,
+    I: ArrayIndex,
+    S: BuildHasher,
+{
+    fn get(&mut self, k: &K) -> Option<&V> {
+        ArrayLruCache::get(self, k)
+    }
+
+    fn insert(&mut self, k: K, v: V) -> Option<V> {
+        ArrayLruCache::insert(self, k, v)
+    }
+
+    fn remove(&mut self, k: &K) -> Option<V> {
+        ArrayLruCache::remove(self, k)
+    }
+
+    fn len(&self) -> usize {
+        ArrayLruCache::len(self)
+    }
+
+    fn capacity(&self) -> usize {
+        N
+    }
+}
+
+/// Lists the entries from least to most recently used.
+impl<K, V, const N: usize, I, S> fmt::Debug for ArrayLruCache<K, V, N, I, S>
+where
+    K: fmt::Debug,
+    V: fmt::Debug,
+    I: ArrayIndex,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut map = f.debug_map();
+        let mut index = self.head.get();
+
+        while let Some(i) = index {
+            let node = &self.nodes[i];
+            let (k, v) = node.entry.as_ref().unwrap();
+            map.entry(k, v);
+            index = node.next.get();
+        }
+
+        map.finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{collections::hash_map::DefaultHasher, format, sync::Mutex};
+
+    use crate::{tests::xorshift, LruCache};
+
+    use super::*;
+
+    /// A hasher that can be built in a `const`.
+    #[derive(Clone, Copy, Default)]
+    struct Fixed;
+
+    impl BuildHasher for Fixed {
+        type Hasher = DefaultHasher;
+
+        fn build_hasher(&self) -> DefaultHasher {
+            DefaultHasher::new()
+        }
+    }
+
+    static SHARED: Mutex<ArrayLruCache<u32, u32, 4, u16, Fixed>> =
+        Mutex::new(ArrayLruCache::with_hasher(Fixed));
+
+    #[test]
+    fn test_cache() {
+        let mut cache = ArrayLruCache::<_, _, 3>::new();
+        cache.insert("a", 1);
+        cache.insert("b", 2);
+        cache.insert("c", 3);
+        assert_eq!(cache.get("a"), Some(&1));
+        assert_eq!(cache.insert("d", 4), None);
+        assert!(!cache.contains_key("b"));
+        assert_eq!(cache.push("e", 5), Some(("c", 3)));
+        assert_eq!(cache.insert("a", 10), Some(1));
+        assert_eq!(format!("{cache:?}"), r#"{"d": 4, "e": 5, "a": 10}"#);
+
+        assert_eq!(cache.remove("e"), Some(5));
+        assert_eq!(cache.remove("e"), None);
+        assert_eq!(cache.len(), 2);
+        assert_eq!(cache.peek_lru(), Some((&"d", &4)));
+
+        cache.clear();
+        assert!(cache.is_empty());
+        cache.insert("f", 6);
+        assert_eq!(format!("{cache:?}"), r#"{"f": 6}"#);
+    }
+
+    #[test]
+    fn test_zero_capacity() {
+        let mut cache = ArrayLruCache::<u32, u32, 0>::new();
+        assert_eq!(cache.insert(1, 1), None);
+        assert_eq!(cache.push(1, 1), Some((1, 1)));
+        assert_eq!(cache.get(&1), None);
+        assert!(cache.is_empty());
+    }
+
+    #[test]
+    fn test_static() {
+        const EMPTY: ArrayLruCache<u32, u32, 8, u16, Fixed> = ArrayLruCache::with_hasher(Fixed);
+
+        let mut cache = EMPTY;
+        cache.insert(1, 1);
+        assert_eq!(cache.get(&1), Some(&1));
+
+        let mut shared = SHARED.lock().unwrap();
+
+        for k in 0..10 {
+            shared.insert(k, k * 2);
+        }
+
+        assert_eq!(format!("{shared:?}"), "{6: 12, 7: 14, 8: 16, 9: 18}");
+    }
+
+    #[test]
+    fn test_matches_lru_cache() {
+        let mut cache = ArrayLruCache::<u32, u32, 16, u16>::new();
+        let mut model = LruCache::new(16);
+
+        for state in xorshift(0x2545_f491).take(10_000) {
+            let k = state % 40;
+
+            match state >> 30 {
+                0 => assert_eq!(cache.remove(&k), model.remove(&k)),
+                1 => assert_eq!(cache.get(&k), model.get(&k)),
+                _ => assert_eq!(cache.push(k, state), model.push(k, state)),
+            }
+
+            assert_eq!(cache.len(), model.len());
+            assert_eq!(cache.peek_lru(), model.peek_lru());
+        }
+    }
+}
@@ -0,0 +1,124 @@
+#[cfg(all(target_has_atomic = "64", target_has_atomic = "ptr"))]
+use alloc::sync::Arc;
+#[cfg(all(target_has_atomic = "64", target_has_atomic = "ptr"))]
+use core::sync::atomic::{AtomicU64, Ordering};
+use core::time::Duration;
+#[cfg(feature = "std")]
+use std::time::Instant;
+
+/// A source of monotonic time used to expire cache entries.
+///
+/// `now` returns the time elapsed since an arbitrary but fixed origin; it must
+/// never go backwards.
+pub trait Clock {
+    fn now(&self) -> Duration;
+}
+
+/// The clock a cache starts with.
+#[cfg(feature = "std")]
+pub(crate) type DefaultClock = MonotonicClock;
+#[cfg(not(feature = "std"))]
+pub(crate) type DefaultClock = StoppedClock;
+
+/// The default clock, backed by [`Instant`].
+#[cfg(feature = "std")]
+#[derive(Clone, Copy, Debug)]
+pub struct MonotonicClock {
+    origin: Instant,
+}
+
+#[cfg(feature = "std")]
+impl MonotonicClock {
+    pub fn new() -> Self {
+        Self {
+            origin: Instant::now(),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl Default for MonotonicClock {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(feature = "std")]
+impl Clock for MonotonicClock {
+    fn now(&self) -> Duration {
+        self.origin.elapsed()
+    }
+}
+
+/// The default clock without `std`, which never moves: entries only expire
+/// once a working clock is set with
+/// [`LruCache::set_clock`](crate::LruCache::set_clock).
+#[cfg(not(feature = "std"))]
+#[derive(Clone, Copy, Debug, Default)]
+pub(crate) struct StoppedClock;
+
+#[cfg(not(feature = "std"))]
+impl Clock for StoppedClock {
+    fn now(&self) -> Duration {
+        Duration::ZERO
+    }
+}
+
+/// A clock that only moves when told to, for testing expiry without sleeping.
+///
+/// Clones share the same time, so a test can keep one handle and give another
+/// to the cache.
+#[cfg(all(target_has_atomic = "64", target_has_atomic = "ptr"))]
+#[derive(Clone, Debug, Default)]
+pub struct ManualClock {
+    nanos: Arc<AtomicU64>,
+}
+
+#[cfg(all(target_has_atomic = "64", target_has_atomic = "ptr"))]
+impl ManualClock {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Moves the clock forward, stopping at the latest time it can tell.
+    pub fn advance(&self, duration: Duration) {
+        let nanos = as_nanos(duration);
+        let _ = self
+            .nanos
+            .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |now| {
+                Some(now.saturating_add(nanos))
+            });
+    }
+
+    pub fn set(&self, now: Duration) {
+        self.nanos.store(as_nanos(now), Ordering::SeqCst);
+    }
+}
+
+#[cfg(all(target_has_atomic = "64", target_has_atomic = "ptr"))]
+impl Clock for ManualClock {
+    fn now(&self) -> Duration {
+        Duration::from_nanos(self.nanos.load(Ordering::SeqCst))
+    }
+}
+
+#[cfg(all(target_has_atomic = "64", target_has_atomic = "ptr"))]
+fn as_nanos(duration: Duration) -> u64 {
+    duration.as_nanos().try_into().unwrap_or(u64::MAX)
+}
+
+#[cfg(all(test, target_has_atomic = "64", target_has_atomic = "ptr"))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_manual_clock_saturates() {
+        let clock = ManualClock::new();
+        clock.advance(Duration::from_secs(1));
+        assert_eq!(clock.now(), Duration::from_secs(1));
+
+        clock.set(Duration::MAX);
+        clock.advance(Duration::from_secs(1));
+        assert_eq!(clock.now(), Duration::from_nanos(u64::MAX));
+    }
+}
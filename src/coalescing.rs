@@ -0,0 +1,549 @@
+use std::{
+    any::Any,
+    borrow::Borrow,
+    collections::HashMap,
+    convert::Infallible,
+    future::{poll_fn, Future},
+    hash::Hash,
+    mem, ptr,
+    sync::{Arc, Mutex},
+    task::{Poll, Waker},
+    vec::Vec,
+};
+
+use crate::{concurrent::lock, LruCache};
+
+type SharedError = Arc<dyn Any + Send + Sync>;
+
+/// A thread-safe [`LruCache`] for async code that runs at most one loader per
+/// missing key at a time.
+///
+/// When several tasks miss the same key together, the first one runs its
+/// loader and the others await its outcome, errors included. If that task is
+/// dropped before its loader completes, one of the waiting tasks runs its own
+/// loader instead.
+///
+/// It does not depend on any particular runtime. Reads hand out clones of the
+/// stored values; store `Arc<V>` to make them cheap.
+pub struct AsyncLruCache<K, V> {
+    state: Mutex<State<K, V>>,
+}
+
+struct State<K, V> {
+    cache: LruCache<K, V>,
+    loads: HashMap<K, Arc<Load<V>>>,
+}
+
+/// A load in progress, shared by the task running it and the tasks awaiting
+/// it.
+type Load<V> = Mutex<Outcome<V>>;
+
+enum Outcome<V> {
+    Pending(Vec<Waker>),
+    Loaded(V),
+    Failed(SharedError),
+    Abandoned,
+}
+
+enum Role<V> {
+    Lead(Arc<Load<V>>),
+    Wait(Arc<Load<V>>),
+}
+
+impl<K, V> AsyncLruCache<K, V>
+where
+    K: Eq + Hash + Clone,
+    V: Clone,
+{
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            state: Mutex::new(State {
+                cache: LruCache::new(capacity),
+                loads: HashMap::new(),
+            }),
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        lock(&self.state).cache.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        lock(&self.state).cache.is_empty()
+    }
+
+    pub fn capacity(&self) -> usize {
+        lock(&self.state).cache.capacity()
+    }
+
+    /// Returns a clone of the value for a key, promoting it. Loads in
+    /// progress are not waited for.
+    pub fn get<Q>(&self, k: &Q) -> Option<V>
+    where
+        K: Borrow<Q>,
+        Q: ?Sized + Hash + Eq,
+    {
+        lock(&self.state).cache.get(k).cloned()
+    }
+
+    /// Inserts a key-value pair, returning the previous value for the key.
+    ///
+    /// A load in progress for the key is superseded: the tasks awaiting it
+    /// still get its value, but it is not cached.
+    pub fn insert(&self, k: K, v: V) -> Option<V> {
+        let mut state = lock(&self.state);
+        state.loads.remove(&k);
+        state.cache.insert(k, v)
+    }
+
+    /// Removes the entry for a key, returning its value. A load in progress
+    /// for the key is superseded like with [`insert`](Self::insert).
+    pub fn remove<Q>(&self, k: &Q) -> Option<V>
+    where
+        K: Borrow<Q>,
+        Q: ?Sized + Hash + Eq,
+    {
+        let mut state = lock(&self.state);
+        state.loads.remove(k);
+        state.cache.remove(k)
+    }
+
+    /// Returns a clone of the value for a key, awaiting `init` to load and
+    /// insert it if it is absent.
+    ///
+    /// `init` is only awaited if no other task is loading the key already,
+    /// otherwise that task's value is returned.
+    pub async fn get_with<F>(&self, k: K, init: F) -> V
+    where
+        F: Future<Output = V>,
+    {
+        match self
+            .try_get_with(k, async { Ok::<_, Infallible>(init.await) })
+            .await
+        {
+            Ok(v) => v,
+            Err(never) => match *never {},
+        }
+    }
+
+    /// Like [`get_with`](Self::get_with), but the load may fail. A failed
+    /// load inserts nothing, and its error is returned to the task that ran
+    /// it and to every task awaiting it.
+    ///
+    /// A task awaiting a load whose error is of another type than `E` runs
+    /// `init` itself instead.
+    pub async fn try_get_with<F, E>(&self, k: K, init: F) -> Result<V, Arc<E>>
+    where
+        F: Future<Output = Result<V, E>>,
+        E: Send + Sync + 'static,
+    {
+        let mut init = Some(init);
+
+        loop {
+            let load = match self.join(&k) {
+                Ok(v) => return Ok(v),
+                Err(Role::Lead(load)) => {
+                    let init = init.take().unwrap();
+                    return self.lead(k, &load, init).await;
+                }
+                Err(Role::Wait(load)) => load,
+            };
+
+            match wait(&load).await {
+                Some(Ok(v)) => return Ok(v),
+                Some(Err(error)) => {
+                    if let Ok(error) = error.downcast::<E>() {
+                        return Err(error);
+                    }
+                }
+                // The leading task was dropped, try again.
+                None => {}
+            }
+        }
+    }
+
+    /// Returns the cached value for a key, or the load to run or await.
+    fn join(&self, k: &K) -> Result<V, Role<V>> {
+        let mut state = lock(&self.state);
+
+        if let Some(v) = state.cache.get(k) {
+            return Ok(v.clone());
+        }
+
+        if let Some(load) = state.loads.get(k) {
+            return Err(Role::Wait(load.clone()));
+        }
+
+        let load = Arc::new(Mutex::new(Outcome::Pending(Vec::new())));
+        state.loads.insert(k.clone(), load.clone());
+        Err(Role::Lead(load))
+    }
+
+    async fn lead<F, E>(&self, k: K, load: &Load<V>, init: F) -> Result<V, Arc<E>>
+    where
+        F: Future<Output = Result<V, E>>,
+        E: Send + Sync + 'static,
+    {
+        // Settles the load as abandoned if this future is dropped while
+        // awaiting `init`.
+        let mut lead = Lead {
+            cache: self,
+            key: k,
+            load,
+            outcome: None,
+        };
+
+        let result = init.await.map_err(Arc::new);
+        lead.outcome = Some(match &result {
+            Ok(v) => Outcome::Loaded(v.clone()),
+            Err(error) => Outcome::Failed(error.clone()),
+        });
+
+        result
+    }
+
+    /// Publishes the outcome of a load, caching its value unless the load was
+    /// superseded, and wakes the tasks awaiting it.
+    fn settle(&self, k: &K, load: &Load<V>, outcome: Outcome<V>) {
+        {
+            let mut state = lock(&self.state);
+            let current = matches!(
+                state.loads.get(k),
+                Some(current) if ptr::eq(Arc::as_ptr(current), load)
+            );
+
+            if current {
+                state.loads.remove(k);
+
+                if let Outcome::Loaded(v) = &outcome {
+                    state.cache.insert(k.clone(), v.clone());
+                }
+            }
+        }
+
+        if let Outcome::Pending(wakers) = mem::replace(&mut *lock(load), outcome) {
+            for waker in wakers {
+                waker.wake();
+            }
+        }
+    }
+}
+
+/// Awaits the outcome of a load, or `None` if it was abandoned.
+async fn wait<V>(load: &Load<V>) -> Option<Result<V, SharedError>>
+where
+    V: Clone,
+{
+    poll_fn(|cx| match &mut *lock(load) {
+        Outcome::Pending(wakers) => {
+            if !wakers.iter().any(|waker| waker.will_wake(cx.waker())) {
+                wakers.push(cx.waker().clone());
+            }
+
+            Poll::Pending
+        }
+        Outcome::Loaded(v) => Poll::Ready(Some(Ok(v.clone()))),
+        Outcome::Failed(error) => Poll::Ready(Some(Err(error.clone()))),
+        Outcome::Abandoned => Poll::Ready(None),
+    })
+    .await
+}
+
+struct Lead<'a, K, V>
+where
+    K: Eq + Hash + Clone,
+    V: Clone,
+{
+    cache: &'a AsyncLruCache<K, V>,
+    key: K,
+    load: &'a Load<V>,
+    outcome: Option<Outcome<V>>,
+}
+
+impl<K, V> Drop for Lead<'_, K, V>
+where
+    K: Eq + Hash + Clone,
+    V: Clone,
+{
+    fn drop(&mut self) {
+        let outcome = self.outcome.take().unwrap_or(Outcome::Abandoned);
+        self.cache.settle(&self.key, self.load, outcome);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        boxed::Box,
+        cell::{Cell, RefCell},
+        pin::Pin,
+        rc::Rc,
+        string::{String, ToString},
+        sync::atomic::{AtomicBool, Ordering},
+        task::{Context, Wake},
+        vec,
+    };
+
+    use super::*;
+
+    type Task<'a> = (Pin<Box<dyn Future<Output = ()> + 'a>>, Arc<Flag>);
+
+    /// A single-threaded executor that only polls tasks that were woken.
+    #[derive(Default)]
+    struct Executor<'a> {
+        tasks: Vec<Option<Task<'a>>>,
+    }
+
+    #[derive(Default)]
+    struct Flag(AtomicBool);
+
+    impl Wake for Flag {
+        fn wake(self: Arc<Self>) {
+            self.0.store(true, Ordering::SeqCst);
+        }
+    }
+
+    impl<'a> Executor<'a> {
+        fn spawn(&mut self, future: impl Future<Output = ()> + 'a) -> usize {
+            let flag = Arc::new(Flag(AtomicBool::new(true)));
+            self.tasks.push(Some((Box::pin(future), flag)));
+            self.tasks.len() - 1
+        }
+
+        fn cancel(&mut self, task: usize) {
+            self.tasks[task] = None;
+        }
+
+        /// Polls woken tasks until none is left, returning how many are still
+        /// pending.
+        fn run_until_stalled(&mut self) -> usize {
+            loop {
+                let mut polled = false;
+
+                for slot in &mut self.tasks {
+                    let Some((future, flag)) = slot else {
+                        continue;
+                    };
+
+                    if !flag.0.swap(false, Ordering::SeqCst) {
+                        continue;
+                    }
+
+                    polled = true;
+                    let waker = Waker::from(flag.clone());
+
+                    if future
+                        .as_mut()
+                        .poll(&mut Context::from_waker(&waker))
+                        .is_ready()
+                    {
+                        *slot = None;
+                    }
+                }
+
+                if !polled {
+                    return self.tasks.iter().flatten().count();
+                }
+            }
+        }
+    }
+
+    #[derive(Clone, Default)]
+    struct Gate(Rc<RefCell<(bool, Option<Waker>)>>);
+
+    impl Gate {
+        fn open(&self) {
+            let mut gate = self.0.borrow_mut();
+            gate.0 = true;
+
+            if let Some(waker) = gate.1.take() {
+                waker.wake();
+            }
+        }
+
+        async fn pass(&self) {
+            poll_fn(|cx| {
+                let mut gate = self.0.borrow_mut();
+
+                if gate.0 {
+                    return Poll::Ready(());
+                }
+
+                gate.1 = Some(cx.waker().clone());
+                Poll::Pending
+            })
+            .await
+        }
+    }
+
+    #[test]
+    fn test_send_sync() {
+        fn assert_send_sync<T: Send + Sync>(_: &T) {}
+
+        let cache = AsyncLruCache::<String, Vec<u8>>::new(1);
+        assert_send_sync(&cache);
+        assert_send_sync(&cache.get_with("k".to_string(), async { Vec::new() }));
+    }
+
+    #[test]
+    fn test_coalesces_loads() {
+        let cache = AsyncLruCache::new(2);
+        let loads = Cell::new(0);
+        let results = RefCell::new(Vec::new());
+        let gate = Gate::default();
+        let mut executor = Executor::default();
+
+        for _ in 0..3 {
+            executor.spawn(async {
+                let v = cache
+                    .get_with(1, async {
+                        loads.set(loads.get() + 1);
+                        gate.pass().await;
+                        10
+                    })
+                    .await;
+                results.borrow_mut().push(v);
+            });
+        }
+
+        assert_eq!(executor.run_until_stalled(), 3);
+        assert_eq!(loads.get(), 1);
+        assert_eq!(cache.get(&1), None);
+
+        gate.open();
+        assert_eq!(executor.run_until_stalled(), 0);
+        assert_eq!(loads.get(), 1);
+        assert_eq!(*results.borrow(), [10, 10, 10]);
+        assert_eq!(cache.get(&1), Some(10));
+    }
+
+    #[test]
+    fn test_propagates_errors() {
+        let cache = AsyncLruCache::<u32, u32>::new(2);
+        let results = RefCell::new(Vec::new());
+        let gate = Gate::default();
+        let mut executor = Executor::default();
+
+        for _ in 0..3 {
+            executor.spawn(async {
+                let result = cache
+                    .try_get_with(1, async {
+                        gate.pass().await;
+                        Err("unavailable")
+                    })
+                    .await;
+                results.borrow_mut().push(result);
+            });
+        }
+
+        executor.run_until_stalled();
+        gate.open();
+        assert_eq!(executor.run_until_stalled(), 0);
+        assert_eq!(*results.borrow(), vec![Err(Arc::new("unavailable")); 3]);
+        assert!(cache.is_empty());
+
+        let mut executor = Executor::default();
+        executor.spawn(async {
+            let v = cache.try_get_with(1, async { Ok::<_, ()>(1) }).await;
+            assert_eq!(v, Ok(1));
+        });
+        assert_eq!(executor.run_until_stalled(), 0);
+    }
+
+    #[test]
+    fn test_remove_supersedes_load() {
+        let cache = AsyncLruCache::new(2);
+        let results = RefCell::new(Vec::new());
+        let gate = Gate::default();
+        let mut executor = Executor::default();
+
+        for _ in 0..2 {
+            executor.spawn(async {
+                let v = cache
+                    .get_with(1, async {
+                        gate.pass().await;
+                        10
+                    })
+                    .await;
+                results.borrow_mut().push(v);
+            });
+        }
+
+        assert_eq!(executor.run_until_stalled(), 2);
+        cache.insert(1, 5);
+        assert_eq!(cache.remove(&1), Some(5));
+
+        gate.open();
+        assert_eq!(executor.run_until_stalled(), 0);
+        assert_eq!(*results.borrow(), [10, 10]);
+        assert_eq!(cache.get(&1), None);
+
+        let mut executor = Executor::default();
+        executor.spawn(async {
+            assert_eq!(cache.get_with(1, async { 20 }).await, 20);
+        });
+        assert_eq!(executor.run_until_stalled(), 0);
+        assert_eq!(cache.get(&1), Some(20));
+    }
+
+    #[test]
+    fn test_insert_supersedes_load() {
+        let cache = AsyncLruCache::new(2);
+        let gate = Gate::default();
+        let mut executor = Executor::default();
+
+        executor.spawn(async {
+            let v = cache
+                .get_with(1, async {
+                    gate.pass().await;
+                    10
+                })
+                .await;
+            assert_eq!(v, 10);
+        });
+
+        assert_eq!(executor.run_until_stalled(), 1);
+        cache.insert(1, 30);
+        gate.open();
+        assert_eq!(executor.run_until_stalled(), 0);
+        assert_eq!(cache.get(&1), Some(30));
+    }
+
+    #[test]
+    fn test_waiter_takes_over_from_dropped_leader() {
+        let cache = AsyncLruCache::new(2);
+        let result = Cell::new(None);
+        let (first, second) = (Gate::default(), Gate::default());
+        let mut executor = Executor::default();
+
+        let leader = executor.spawn(async {
+            cache
+                .get_with(1, async {
+                    first.pass().await;
+                    10
+                })
+                .await;
+            unreachable!();
+        });
+        executor.spawn(async {
+            let v = cache
+                .get_with(1, async {
+                    second.pass().await;
+                    20
+                })
+                .await;
+            result.set(Some(v));
+        });
+
+        assert_eq!(executor.run_until_stalled(), 2);
+        executor.cancel(leader);
+        assert_eq!(executor.run_until_stalled(), 1);
+
+        first.open();
+        assert_eq!(executor.run_until_stalled(), 1);
+        second.open();
+        assert_eq!(executor.run_until_stalled(), 0);
+        assert_eq!(result.get(), Some(20));
+        assert_eq!(cache.get(&1), Some(20));
+    }
+}
@@ -0,0 +1,244 @@
+use std::{
+    borrow::Borrow,
+    boxed::Box,
+    collections::hash_map::RandomState,
+    hash::Hash,
+    sync::{Mutex, MutexGuard},
+    thread,
+    time::Duration,
+};
+
+use crate::{make_hash, LruCache};
+
+/// The least capacity worth giving a shard. Below it, a shard evicts long
+/// before the cache as a whole is full, so smaller caches get fewer shards.
+const MIN_SHARD_CAPACITY: usize = 16;
+
+/// A thread-safe LRU cache split into independently locked shards.
+///
+/// Every key is routed to one shard by its hash and each shard is an
+/// [`LruCache`] behind its own mutex, so threads working on different shards
+/// never contend. Recency and eviction are tracked per shard: the least
+/// recently used entry of the shard a new key lands in is evicted, which is
+/// not necessarily the least recently used entry overall.
+///
+/// Reads hand out clones of the stored values; store `Arc<V>` to make them
+/// cheap.
+pub struct ConcurrentLruCache<K, V> {
+    shards: Box<[Mutex<LruCache<K, V>>]>,
+    hash_builder: RandomState,
+}
+
+impl<K, V> ConcurrentLruCache<K, V>
+where
+    K: Eq + Hash,
+{
+    /// Creates a cache holding at most `capacity` entries in total, with up to
+    /// four shards per available core.
+    pub fn new(capacity: usize) -> Self {
+        let cores = thread::available_parallelism().map_or(1, |n| n.get());
+        Self::with_shards(capacity, cores * 4)
+    }
+
+    /// Creates a cache holding at most `capacity` entries in total, spread
+    /// over up to `shards` shards.
+    ///
+    /// Fewer shards are used if some would hold less than 16 entries, down to
+    /// a single one for small caches, so that no key lands in a shard too
+    /// small to keep it.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `shards` is zero.
+    pub fn with_shards(capacity: usize, shards: usize) -> Self {
+        assert!(shards > 0, "a concurrent cache needs at least one shard");
+
+        let shards = shards.min(capacity / MIN_SHARD_CAPACITY).max(1);
+        let shards = (0..shards)
+            .map(|i| {
+                let extra = usize::from(i < capacity % shards);
+                Mutex::new(LruCache::new(capacity / shards + extra))
+            })
+            .collect();
+
+        Self {
+            shards,
+            hash_builder: RandomState::new(),
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.shards.iter().map(|shard| lock(shard).len()).sum()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.shards.iter().all(|shard| lock(shard).is_empty())
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.shards.iter().map(|shard| lock(shard).capacity()).sum()
+    }
+
+    /// Returns a clone of the value for a key, promoting it within its shard.
+    pub fn get<Q>(&self, k: &Q) -> Option<V>
+    where
+        K: Borrow<Q>,
+        Q: ?Sized + Hash + Eq,
+        V: Clone,
+    {
+        self.shard(k).get(k).cloned()
+    }
+
+    /// Returns a clone of the value for a key without promoting it.
+    pub fn peek<Q>(&self, k: &Q) -> Option<V>
+    where
+        K: Borrow<Q>,
+        Q: ?Sized + Hash + Eq,
+        V: Clone,
+    {
+        self.shard(k).peek(k).cloned()
+    }
+
+    pub fn contains_key<Q>(&self, k: &Q) -> bool
+    where
+        K: Borrow<Q>,
+        Q: ?Sized + Hash + Eq,
+    {
+        self.shard(k).contains_key(k)
+    }
+
+    /// Inserts a key-value pair, returning the previous value for the key.
+    pub fn insert(&self, k: K, v: V) -> Option<V> {
+        self.shard(&k).insert(k, v)
+    }
+
+    /// Inserts a key-value pair that expires after `ttl`, returning the
+    /// previous value for the key.
+    pub fn insert_with_ttl(&self, k: K, v: V, ttl: Duration) -> Option<V> {
+        self.shard(&k).insert_with_ttl(k, v, ttl)
+    }
+
+    /// Inserts a key-value pair, returning the pair it displaced. See
+    /// [`LruCache::push`].
+    pub fn push(&self, k: K, v: V) -> Option<(K, V)> {
+        self.shard(&k).push(k, v)
+    }
+
+    pub fn remove<Q>(&self, k: &Q) -> Option<V>
+    where
+        K: Borrow<Q>,
+        Q: ?Sized + Hash + Eq,
+    {
+        self.shard(k).remove(k)
+    }
+
+    /// Removes every expired entry, one shard at a time, returning how many
+    /// there were.
+    pub fn purge_expired(&self) -> usize {
+        self.shards
+            .iter()
+            .map(|shard| lock(shard).purge_expired())
+            .sum()
+    }
+
+    fn shard<Q>(&self, k: &Q) -> MutexGuard<'_, LruCache<K, V>>
+    where
+        Q: ?Sized + Hash,
+    {
+        let hash = make_hash(&self.hash_builder, k);
+        lock(&self.shards[hash as usize % self.shards.len()])
+    }
+}
+
+pub(crate) fn lock<T>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+    mutex.lock().unwrap()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{string::String, sync::Arc, thread, vec::Vec};
+
+    use super::*;
+
+    #[test]
+    fn test_send_sync() {
+        fn assert_send_sync<T: Send + Sync>() {}
+
+        assert_send_sync::<ConcurrentLruCache<String, Vec<u8>>>();
+    }
+
+    #[test]
+    fn test_capacity_split() {
+        let cache = ConcurrentLruCache::<u32, u32>::with_shards(100, 4);
+        assert_eq!(cache.shards.len(), 4);
+        assert_eq!(cache.capacity(), 100);
+
+        let cache = ConcurrentLruCache::<u32, u32>::with_shards(50, 8);
+        assert_eq!(cache.shards.len(), 3);
+        assert_eq!(cache.capacity(), 50);
+
+        let cache = ConcurrentLruCache::<u32, u32>::with_shards(10, 4);
+        assert_eq!(cache.shards.len(), 1);
+        assert_eq!(cache.capacity(), 10);
+
+        let cache = ConcurrentLruCache::<u32, u32>::with_shards(0, 4);
+        assert_eq!(cache.shards.len(), 1);
+        assert_eq!(cache.capacity(), 0);
+    }
+
+    #[test]
+    fn test_small_cache_keeps_every_key() {
+        for (capacity, shards) in [(2, 4), (10, 16), (15, 64)] {
+            let cache = ConcurrentLruCache::with_shards(capacity, shards);
+
+            for k in 0..capacity {
+                cache.insert(k, k);
+            }
+
+            assert_eq!(cache.len(), capacity);
+            assert!((0..capacity).all(|k| cache.contains_key(&k)));
+        }
+
+        let cache = ConcurrentLruCache::<u32, u32>::new(100);
+        assert_eq!(cache.capacity(), 100);
+        assert!(cache.shards.len() <= 6);
+    }
+
+    #[test]
+    fn test_single_shard_is_lru() {
+        let cache = ConcurrentLruCache::with_shards(2, 1);
+
+        cache.insert(1, "a");
+        cache.insert(2, "b");
+        assert_eq!(cache.get(&1), Some("a"));
+
+        cache.insert(3, "c");
+        assert_eq!(cache.peek(&2), None);
+        assert!(cache.contains_key(&1));
+        assert_eq!(cache.remove(&3), Some("c"));
+        assert_eq!(cache.len(), 1);
+    }
+
+    #[test]
+    fn test_threads() {
+        let cache = ConcurrentLruCache::with_shards(1_000, 8);
+
+        thread::scope(|s| {
+            for t in 0..4 {
+                let cache = &cache;
+
+                s.spawn(move || {
+                    for k in (t * 100)..(t * 100 + 100) {
+                        cache.insert(k, Arc::new(k * 2));
+                    }
+                });
+            }
+        });
+
+        assert_eq!(cache.len(), 400);
+
+        for k in 0..400 {
+            assert_eq!(cache.get(&k).as_deref(), Some(&(k * 2)));
+        }
+    }
+}
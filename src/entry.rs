@@ -0,0 +1,192 @@
+use core::hash::{BuildHasher, Hash};
+
+use crate::{
+    policy::Lru, DefaultHashBuilder, EvictionPolicy, EvictionReason, LruCache, NoStats, NodeId,
+    StatsRecorder,
+};
+
+/// A view into a single entry of an [`LruCache`], obtained from
+/// [`LruCache::entry`].
+pub enum Entry<'a, K, V, P = Lru, R = NoStats, S = DefaultHashBuilder> {
+    Occupied(OccupiedEntry<'a, K, V, P, R, S>),
+    Vacant(VacantEntry<'a, K, V, P, R, S>),
+}
+
+/// An entry whose key is present. It has already been promoted to most
+/// recently used.
+pub struct OccupiedEntry<'a, K, V, P = Lru, R = NoStats, S = DefaultHashBuilder> {
+    cache: &'a mut LruCache<K, V, P, R, S>,
+    hash: u64,
+    node_id: NodeId,
+}
+
+/// An entry whose key is absent. Inserting into it may evict the least
+/// recently used entry.
+pub struct VacantEntry<'a, K, V, P = Lru, R = NoStats, S = DefaultHashBuilder> {
+    cache: &'a mut LruCache<K, V, P, R, S>,
+    hash: u64,
+    key: K,
+}
+
+impl<'a, K, V, P, R, S> Entry<'a, K, V, P, R, S>
+where
+    K: Eq + Hash,
+    P: EvictionPolicy,
+    R: StatsRecorder,
+    S: BuildHasher,
+{
+    pub fn key(&self) -> &K {
+        match self {
+            Entry::Occupied(entry) => entry.key(),
+            Entry::Vacant(entry) => entry.key(),
+        }
+    }
+
+    /// Returns the value, inserting `default` if the key is absent. See
+    /// [`VacantEntry::insert`] for when the pair is handed back instead.
+    pub fn or_insert(self, default: V) -> Result<&'a mut V, (K, V)> {
+        match self {
+            Entry::Occupied(entry) => Ok(entry.into_mut()),
+            Entry::Vacant(entry) => entry.insert(default),
+        }
+    }
+
+    pub fn or_insert_with<F>(self, default: F) -> Result<&'a mut V, (K, V)>
+    where
+        F: FnOnce() -> V,
+    {
+        match self {
+            Entry::Occupied(entry) => Ok(entry.into_mut()),
+            Entry::Vacant(entry) => entry.insert(default()),
+        }
+    }
+
+    pub fn or_insert_with_key<F>(self, default: F) -> Result<&'a mut V, (K, V)>
+    where
+        F: FnOnce(&K) -> V,
+    {
+        match self {
+            Entry::Occupied(entry) => Ok(entry.into_mut()),
+            Entry::Vacant(entry) => {
+                let v = default(entry.key());
+                entry.insert(v)
+            }
+        }
+    }
+
+    pub fn or_default(self) -> Result<&'a mut V, (K, V)>
+    where
+        V: Default,
+    {
+        self.or_insert_with(V::default)
+    }
+
+    pub fn and_modify<F>(mut self, f: F) -> Self
+    where
+        F: FnOnce(&mut V),
+    {
+        if let Entry::Occupied(entry) = &mut self {
+            f(entry.get_mut());
+        }
+
+        self
+    }
+}
+
+impl<'a, K, V, P, R, S> OccupiedEntry<'a, K, V, P, R, S>
+where
+    K: Eq + Hash,
+    P: EvictionPolicy,
+    R: StatsRecorder,
+    S: BuildHasher,
+{
+    pub(crate) fn new(cache: &'a mut LruCache<K, V, P, R, S>, hash: u64, node_id: NodeId) -> Self {
+        Self {
+            cache,
+            hash,
+            node_id,
+        }
+    }
+
+    pub fn key(&self) -> &K {
+        &self.cache.graph.node(self.node_id).unwrap().element.key
+    }
+
+    pub fn get(&self) -> &V {
+        &self.cache.graph.node(self.node_id).unwrap().element.value
+    }
+
+    pub fn get_mut(&mut self) -> &mut V {
+        &mut self
+            .cache
+            .graph
+            .node_mut(self.node_id)
+            .unwrap()
+            .element
+            .value
+    }
+
+    pub fn into_mut(self) -> &'a mut V {
+        &mut self
+            .cache
+            .graph
+            .node_mut(self.node_id)
+            .unwrap()
+            .element
+            .value
+    }
+
+    /// Replaces the value, returning the old one.
+    ///
+    /// A new value that cannot be stored is handed back with the key instead,
+    /// and the entry is removed, as with [`LruCache::insert`].
+    pub fn insert(self, v: V) -> Result<V, (K, V)> {
+        let expires_at = self.cache.expiry(self.cache.default_ttl);
+
+        self.cache
+            .replace(self.hash, self.node_id, v, expires_at)
+            .map_err(|(_old_v, rejected)| rejected)
+    }
+
+    pub fn remove(self) -> V {
+        self.remove_entry().1
+    }
+
+    pub fn remove_entry(self) -> (K, V) {
+        self.cache
+            .remove_node(self.hash, self.node_id, EvictionReason::Removed)
+    }
+}
+
+impl<'a, K, V, P, R, S> VacantEntry<'a, K, V, P, R, S>
+where
+    K: Eq + Hash,
+    P: EvictionPolicy,
+    R: StatsRecorder,
+    S: BuildHasher,
+{
+    pub(crate) fn new(cache: &'a mut LruCache<K, V, P, R, S>, hash: u64, key: K) -> Self {
+        Self { cache, hash, key }
+    }
+
+    pub fn key(&self) -> &K {
+        &self.key
+    }
+
+    pub fn into_key(self) -> K {
+        self.key
+    }
+
+    /// Inserts the value as the most recently used entry, evicting the least
+    /// recently used one if the cache is full.
+    ///
+    /// A pair that cannot be stored, as in a zero-capacity cache, is handed
+    /// back instead, after being reported to the eviction listener like
+    /// [`LruCache::insert`] does.
+    pub fn insert(self, v: V) -> Result<&'a mut V, (K, V)> {
+        let expires_at = self.cache.expiry(self.cache.default_ttl);
+        let (node_id, _evicted) = self.cache.push_new(self.hash, self.key, v, expires_at)?;
+
+        Ok(&mut self.cache.graph.node_mut(node_id).unwrap().element.value)
+    }
+}
@@ -0,0 +1,317 @@
+use core::{iter::FusedIterator, marker::PhantomData};
+
+use crate::{Graph, Item, NodeId, Slot};
+
+/// Iterator over the entries of an [`LruCache`](crate::LruCache) from least
+/// to most recently used, created by [`LruCache::iter`](crate::LruCache::iter).
+pub struct Iter<'a, K, V> {
+    graph: &'a Graph<Item<K, V>>,
+    front_id: Option<NodeId>,
+    back_id: Option<NodeId>,
+    len: usize,
+}
+
+impl<'a, K, V> Iter<'a, K, V> {
+    pub(crate) fn new(graph: &'a Graph<Item<K, V>>, len: usize) -> Self {
+        Self {
+            graph,
+            front_id: graph.head_id,
+            back_id: graph.tail_id,
+            len,
+        }
+    }
+}
+
+impl<'a, K, V> Iterator for Iter<'a, K, V> {
+    type Item = (&'a K, &'a V);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.len == 0 {
+            return None;
+        }
+
+        let node = self.graph.node(self.front_id?)?;
+        self.front_id = node.next_id;
+        self.len -= 1;
+
+        Some((&node.element.key, &node.element.value))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.len, Some(self.len))
+    }
+}
+
+impl<K, V> DoubleEndedIterator for Iter<'_, K, V> {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        if self.len == 0 {
+            return None;
+        }
+
+        let node = self.graph.node(self.back_id?)?;
+        self.back_id = node.prev_id;
+        self.len -= 1;
+
+        Some((&node.element.key, &node.element.value))
+    }
+}
+
+impl<K, V> ExactSizeIterator for Iter<'_, K, V> {}
+
+impl<K, V> FusedIterator for Iter<'_, K, V> {}
+
+impl<K, V> Clone for Iter<'_, K, V> {
+    fn clone(&self) -> Self {
+        Self { ..*self }
+    }
+}
+
+/// Mutable iterator over the entries of an [`LruCache`](crate::LruCache) from
+/// least to most recently used, created by
+/// [`LruCache::iter_mut`](crate::LruCache::iter_mut).
+pub struct IterMut<'a, K, V> {
+    nodes: *mut Slot<Item<K, V>>,
+    front_id: Option<NodeId>,
+    back_id: Option<NodeId>,
+    len: usize,
+    _marker: PhantomData<&'a mut Graph<Item<K, V>>>,
+}
+
+// SAFETY: `IterMut` behaves like a `&mut Graph<Item<K, V>>`.
+unsafe impl<K: Send, V: Send> Send for IterMut<'_, K, V> {}
+
+// SAFETY: `IterMut` behaves like a `&mut Graph<Item<K, V>>`.
+unsafe impl<K: Sync, V: Sync> Sync for IterMut<'_, K, V> {}
+
+impl<'a, K, V> IterMut<'a, K, V> {
+    pub(crate) fn new(graph: &'a mut Graph<Item<K, V>>, len: usize) -> Self {
+        Self {
+            nodes: graph.nodes.as_mut_ptr(),
+            front_id: graph.head_id,
+            back_id: graph.tail_id,
+            len,
+            _marker: PhantomData,
+        }
+    }
+
+    fn take(&mut self, node_id: NodeId, forward: bool) -> (&'a K, &'a mut V) {
+        // SAFETY: `node_id` comes from the links of the graph this iterator
+        // exclusively borrows, so it is in bounds and occupied. `len` stops
+        // both ends before they cross, so every node is handed out at most
+        // once and the returned references never alias.
+        let slot = unsafe { &mut *self.nodes.add(node_id) };
+
+        let node = match slot {
+            Slot::Occupied(node) => node,
+            Slot::Vacant { .. } => unreachable!(),
+        };
+
+        if forward {
+            self.front_id = node.next_id;
+        } else {
+            self.back_id = node.prev_id;
+        }
+
+        self.len -= 1;
+
+        (&node.element.key, &mut node.element.value)
+    }
+}
+
+impl<'a, K, V> Iterator for IterMut<'a, K, V> {
+    type Item = (&'a K, &'a mut V);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.len == 0 {
+            return None;
+        }
+
+        let front_id = self.front_id?;
+        Some(self.take(front_id, true))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.len, Some(self.len))
+    }
+}
+
+impl<K, V> DoubleEndedIterator for IterMut<'_, K, V> {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        if self.len == 0 {
+            return None;
+        }
+
+        let back_id = self.back_id?;
+        Some(self.take(back_id, false))
+    }
+}
+
+impl<K, V> ExactSizeIterator for IterMut<'_, K, V> {}
+
+impl<K, V> FusedIterator for IterMut<'_, K, V> {}
+
+/// Iterator over the keys of an [`LruCache`](crate::LruCache) from least to
+/// most recently used.
+pub struct Keys<'a, K, V> {
+    pub(crate) inner: Iter<'a, K, V>,
+}
+
+impl<'a, K, V> Iterator for Keys<'a, K, V> {
+    type Item = &'a K;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.inner.next().map(|(k, _v)| k)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.inner.size_hint()
+    }
+}
+
+impl<K, V> DoubleEndedIterator for Keys<'_, K, V> {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        self.inner.next_back().map(|(k, _v)| k)
+    }
+}
+
+impl<K, V> ExactSizeIterator for Keys<'_, K, V> {}
+
+impl<K, V> FusedIterator for Keys<'_, K, V> {}
+
+/// Iterator over the values of an [`LruCache`](crate::LruCache) from least to
+/// most recently used.
+pub struct Values<'a, K, V> {
+    pub(crate) inner: Iter<'a, K, V>,
+}
+
+impl<'a, K, V> Iterator for Values<'a, K, V> {
+    type Item = &'a V;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.inner.next().map(|(_k, v)| v)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.inner.size_hint()
+    }
+}
+
+impl<K, V> DoubleEndedIterator for Values<'_, K, V> {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        self.inner.next_back().map(|(_k, v)| v)
+    }
+}
+
+impl<K, V> ExactSizeIterator for Values<'_, K, V> {}
+
+impl<K, V> FusedIterator for Values<'_, K, V> {}
+
+/// Mutable iterator over the values of an [`LruCache`](crate::LruCache) from
+/// least to most recently used.
+pub struct ValuesMut<'a, K, V> {
+    pub(crate) inner: IterMut<'a, K, V>,
+}
+
+impl<'a, K, V> Iterator for ValuesMut<'a, K, V> {
+    type Item = &'a mut V;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.inner.next().map(|(_k, v)| v)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.inner.size_hint()
+    }
+}
+
+impl<K, V> DoubleEndedIterator for ValuesMut<'_, K, V> {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        self.inner.next_back().map(|(_k, v)| v)
+    }
+}
+
+impl<K, V> ExactSizeIterator for ValuesMut<'_, K, V> {}
+
+impl<K, V> FusedIterator for ValuesMut<'_, K, V> {}
+
+/// Owning iterator over the entries of an [`LruCache`](crate::LruCache) from
+/// least to most recently used.
+pub struct IntoIter<K, V> {
+    graph: Graph<Item<K, V>>,
+    len: usize,
+}
+
+impl<K, V> IntoIter<K, V> {
+    pub(crate) fn new(graph: Graph<Item<K, V>>, len: usize) -> Self {
+        Self { graph, len }
+    }
+}
+
+impl<K, V> Iterator for IntoIter<K, V> {
+    type Item = (K, V);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let item = self.graph.pop_front()?;
+        self.len -= 1;
+        Some((item.key, item.value))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.len, Some(self.len))
+    }
+}
+
+impl<K, V> DoubleEndedIterator for IntoIter<K, V> {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        let item = self.graph.pop_back()?;
+        self.len -= 1;
+        Some((item.key, item.value))
+    }
+}
+
+impl<K, V> ExactSizeIterator for IntoIter<K, V> {}
+
+impl<K, V> FusedIterator for IntoIter<K, V> {}
+
+/// Draining iterator over the entries of an [`LruCache`](crate::LruCache) from
+/// least to most recently used, created by
+/// [`LruCache::drain`](crate::LruCache::drain). Entries not yielded are
+/// dropped along with the iterator.
+///
+/// The entries are moved out of the cache up front, so leaking the iterator
+/// leaks them but leaves the cache empty and consistent.
+pub struct Drain<'a, K, V> {
+    inner: IntoIter<K, V>,
+    marker: PhantomData<&'a mut Graph<Item<K, V>>>,
+}
+
+impl<K, V> Drain<'_, K, V> {
+    pub(crate) fn new(inner: IntoIter<K, V>) -> Self {
+        Self {
+            inner,
+            marker: PhantomData,
+        }
+    }
+}
+
+impl<K, V> Iterator for Drain<'_, K, V> {
+    type Item = (K, V);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.inner.next()
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.inner.size_hint()
+    }
+}
+
+impl<K, V> DoubleEndedIterator for Drain<'_, K, V> {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        self.inner.next_back()
+    }
+}
+
+impl<K, V> ExactSizeIterator for Drain<'_, K, V> {}
+
+impl<K, V> FusedIterator for Drain<'_, K, V> {}
@@ -1,6 +1,60 @@
-#![feature(map_many_mut)]
+#![no_std]
 
-use std::{borrow::Borrow, collections::HashMap, hash::Hash};
+extern crate alloc;
+#[cfg(any(feature = "std", test))]
+extern crate std;
+
+use alloc::{boxed::Box, vec::Vec};
+use core::{
+    borrow::Borrow,
+    convert::Infallible,
+    fmt,
+    hash::{BuildHasher, Hash, Hasher},
+    mem,
+    time::Duration,
+};
+
+use hashbrown::HashTable;
+
+pub use arc::ArcCache;
+pub use array::{ArrayIndex, ArrayLruCache};
+pub use clock::Clock;
+#[cfg(all(target_has_atomic = "64", target_has_atomic = "ptr"))]
+pub use clock::ManualClock;
+#[cfg(feature = "std")]
+pub use clock::MonotonicClock;
+#[cfg(feature = "std")]
+pub use coalescing::AsyncLruCache;
+#[cfg(feature = "std")]
+pub use concurrent::ConcurrentLruCache;
+pub use entry::{Entry, OccupiedEntry, VacantEntry};
+pub use iter::{Drain, IntoIter, Iter, IterMut, Keys, Values, ValuesMut};
+pub use loading::LoadingCache;
+pub use policy::EvictionPolicy;
+pub use stats::{CacheStats, NoStats, StatsCounter, StatsRecorder};
+
+use clock::DefaultClock;
+use policy::Lru;
+
+mod arc;
+mod array;
+mod clock;
+#[cfg(feature = "std")]
+mod coalescing;
+#[cfg(feature = "std")]
+mod concurrent;
+mod entry;
+mod iter;
+mod loading;
+pub mod policy;
+#[cfg(feature = "serde")]
+mod serde_impls;
+mod stats;
+
+/// The hasher used unless another one is given, hashbrown's default hasher
+/// with or without the `std` feature. Std's `RandomState` can be used through
+/// [`LruCache::with_hasher`] and the other `_hasher` constructors.
+pub type DefaultHashBuilder = hashbrown::DefaultHashBuilder;
 
 type NodeId = usize;
 
@@ -11,51 +65,98 @@ struct Node<T> {
     element: T,
 }
 
+#[derive(Debug)]
+enum Slot<T> {
+    Occupied(Node<T>),
+    Vacant { next_free_id: Option<NodeId> },
+}
+
+/// A doubly linked list whose nodes live in a slot arena.
+///
+/// Removed nodes leave a vacant slot behind that is threaded onto a free list
+/// and reused by the next `push_back`, so node ids stay dense and moving a
+/// node around only relinks indices.
 #[derive(Debug)]
 struct Graph<T> {
-    nodes: HashMap<NodeId, Node<T>>,
+    nodes: Vec<Slot<T>>,
+    free_id: Option<NodeId>,
     head_id: Option<NodeId>,
     tail_id: Option<NodeId>,
 }
 
+impl<T> Default for Graph<T> {
+    fn default() -> Self {
+        Self::with_capacity(0)
+    }
+}
+
 impl<T> Graph<T> {
     fn with_capacity(capacity: usize) -> Self {
         Self {
-            nodes: HashMap::with_capacity(capacity),
+            nodes: Vec::with_capacity(capacity),
+            free_id: None,
             head_id: None,
             tail_id: None,
         }
     }
 
-    fn remove(&mut self, node_id: NodeId) -> Option<T> {
-        let node = self.nodes.remove(&node_id)?;
+    fn node(&self, node_id: NodeId) -> Option<&Node<T>> {
+        match self.nodes.get(node_id)? {
+            Slot::Occupied(node) => Some(node),
+            Slot::Vacant { .. } => None,
+        }
+    }
+
+    fn node_mut(&mut self, node_id: NodeId) -> Option<&mut Node<T>> {
+        match self.nodes.get_mut(node_id)? {
+            Slot::Occupied(node) => Some(node),
+            Slot::Vacant { .. } => None,
+        }
+    }
 
-        match (node.prev_id, node.next_id) {
-            (Some(prev_id), Some(next_id)) => {
-                let [prev, next] = self.nodes.get_many_mut([&prev_id, &next_id]).unwrap();
+    fn unlink(&mut self, node_id: NodeId) -> Option<()> {
+        let node = self.node_mut(node_id)?;
+        let prev_id = node.prev_id.take();
+        let next_id = node.next_id.take();
 
-                next.prev_id = Some(prev_id);
-                prev.next_id = Some(next_id);
-            }
-            (Some(prev_id), None) => {
-                let prev = self.nodes.get_mut(&prev_id).unwrap();
+        match prev_id {
+            Some(prev_id) => self.node_mut(prev_id).unwrap().next_id = next_id,
+            None => self.head_id = next_id,
+        }
 
-                prev.next_id = None;
-                self.tail_id = Some(prev_id);
-            }
-            (None, Some(next_id)) => {
-                let next = self.nodes.get_mut(&next_id).unwrap();
+        match next_id {
+            Some(next_id) => self.node_mut(next_id).unwrap().prev_id = prev_id,
+            None => self.tail_id = prev_id,
+        }
 
-                self.head_id = Some(next_id);
-                next.prev_id = None;
-            }
-            (None, None) => {
-                self.head_id = None;
-                self.tail_id = None;
-            }
+        Some(())
+    }
+
+    fn link_back(&mut self, node_id: NodeId) {
+        let tail_id = self.tail_id.replace(node_id);
+
+        match tail_id {
+            Some(tail_id) => self.node_mut(tail_id).unwrap().next_id = Some(node_id),
+            None => self.head_id = Some(node_id),
         }
 
-        Some(node.element)
+        self.node_mut(node_id).unwrap().prev_id = tail_id;
+    }
+
+    fn remove(&mut self, node_id: NodeId) -> Option<T> {
+        self.unlink(node_id)?;
+
+        let slot = mem::replace(
+            &mut self.nodes[node_id],
+            Slot::Vacant {
+                next_free_id: self.free_id.replace(node_id),
+            },
+        );
+
+        match slot {
+            Slot::Occupied(node) => Some(node.element),
+            Slot::Vacant { .. } => unreachable!(),
+        }
     }
 
     fn pop_front(&mut self) -> Option<T> {
@@ -63,125 +164,1285 @@ impl<T> Graph<T> {
         self.remove(head_id)
     }
 
-    fn push_back(&mut self, node_id: NodeId, element: T) {
-        let tail_id = match self.tail_id.replace(node_id) {
-            Some(tail_id) => {
-                let tail = self.nodes.get_mut(&tail_id).unwrap();
-                tail.next_id = Some(node_id);
+    fn pop_back(&mut self) -> Option<T> {
+        let tail_id = self.tail_id?;
+        self.remove(tail_id)
+    }
+
+    fn push_back(&mut self, element: T) -> NodeId {
+        let node = Slot::Occupied(Node {
+            next_id: None,
+            prev_id: None,
+            element,
+        });
+
+        let node_id = match self.free_id {
+            Some(free_id) => {
+                let slot = mem::replace(&mut self.nodes[free_id], node);
 
-                Some(tail_id)
+                match slot {
+                    Slot::Vacant { next_free_id } => self.free_id = next_free_id,
+                    Slot::Occupied(_) => unreachable!(),
+                }
+
+                free_id
             }
             None => {
-                self.head_id = Some(node_id);
-                None
+                self.nodes.push(node);
+                self.nodes.len() - 1
             }
         };
 
-        self.nodes.insert(
-            node_id,
-            Node {
-                next_id: None,
-                prev_id: tail_id,
-                element,
-            },
-        );
+        self.link_back(node_id);
+        node_id
+    }
+
+    fn move_to_back(&mut self, node_id: NodeId) -> Option<()> {
+        if self.tail_id != Some(node_id) {
+            self.unlink(node_id)?;
+            self.link_back(node_id);
+        }
+
+        Some(())
+    }
+
+    fn front(&self) -> Option<&T> {
+        Some(&self.node(self.head_id?)?.element)
     }
 
     fn get(&mut self, node_id: NodeId) -> Option<&T> {
-        let elem = self.remove(node_id)?;
-        self.push_back(node_id, elem);
-        let node = self.nodes.get(&node_id).unwrap();
-        Some(&node.element)
+        self.move_to_back(node_id)?;
+        Some(&self.node(node_id)?.element)
+    }
+
+    fn get_mut(&mut self, node_id: NodeId) -> Option<&mut T> {
+        self.move_to_back(node_id)?;
+        Some(&mut self.node_mut(node_id)?.element)
+    }
+}
+
+/// The operations shared by the cache types in this crate, so that one can be
+/// swapped for another behind a generic parameter or a trait object.
+pub trait Cache<K, V> {
+    /// Returns the value for a key, counting it as used.
+    fn get(&mut self, k: &K) -> Option<&V>;
+
+    /// Inserts a key-value pair, returning the previous value for the key.
+    fn insert(&mut self, k: K, v: V) -> Option<V>;
+
+    fn remove(&mut self, k: &K) -> Option<V>;
+
+    fn len(&self) -> usize;
+
+    fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    fn capacity(&self) -> usize;
+}
+
+/// Why an entry left the cache, as reported to an [`EvictionListener`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum EvictionReason {
+    /// The entry was the least recently used one when room was needed.
+    Capacity,
+    /// The entry was removed explicitly.
+    Removed,
+    /// The entry's value was overwritten by an insert for the same key.
+    Replaced,
+    /// The entry outlived its time-to-live.
+    Expired,
+}
+
+/// Why a cache could not be created by [`LruCache::try_new`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum CapacityError {
+    /// The capacity was zero, so the cache could never hold an entry.
+    Zero,
+    /// Room for the requested number of entries could not be allocated.
+    TooLarge,
+}
+
+impl fmt::Display for CapacityError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CapacityError::Zero => f.write_str("cache capacity is zero"),
+            CapacityError::TooLarge => f.write_str("cache capacity is too large to allocate"),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for CapacityError {}
+
+/// Observes entries as they leave an [`LruCache`].
+///
+/// Implemented for any `FnMut(&K, &V, EvictionReason)` closure.
+pub trait EvictionListener<K, V> {
+    fn on_evict(&mut self, key: &K, value: &V, reason: EvictionReason);
+}
+
+impl<K, V, F> EvictionListener<K, V> for F
+where
+    F: FnMut(&K, &V, EvictionReason),
+{
+    fn on_evict(&mut self, key: &K, value: &V, reason: EvictionReason) {
+        self(key, value, reason)
+    }
+}
+
+/// Measures how much of an [`LruCache`]'s capacity an entry takes up.
+///
+/// Implemented for any `Fn(&K, &V) -> usize` closure.
+pub trait Weigher<K, V> {
+    fn weigh(&self, key: &K, value: &V) -> usize;
+}
+
+impl<K, V, F> Weigher<K, V> for F
+where
+    F: Fn(&K, &V) -> usize,
+{
+    fn weigh(&self, key: &K, value: &V) -> usize {
+        self(key, value)
     }
 }
 
+/// The node of a newly pushed entry and the first pair evicted for it, or the
+/// rejected pair.
+type Pushed<K, V> = Result<(NodeId, Option<(K, V)>), (K, V)>;
+
+/// The old value of a replaced entry, or the old value along with the rejected
+/// pair if the entry had to be removed instead.
+type Replaced<K, V> = Result<V, (V, (K, V))>;
+
+/// The previous value for a key, if any, along with a rejected pair.
+type Rejected<K, V> = (Option<V>, (K, V));
+
 #[derive(Debug)]
-pub struct LruCache<K, V> {
-    node_ids: HashMap<K, NodeId>,
-    graph: Graph<(K, V)>,
-    id: NodeId,
+struct Item<K, V> {
+    key: K,
+    value: V,
+    expires_at: Option<Duration>,
+    weight: usize,
+    pinned: bool,
+}
+
+pub struct LruCache<K, V, P = Lru, R = NoStats, S = DefaultHashBuilder> {
+    node_ids: HashTable<NodeId>,
+    graph: Graph<Item<K, V>>,
+    hash_builder: S,
+    capacity: usize,
+    weigher: Option<Box<dyn Weigher<K, V> + Send + Sync>>,
+    total_weight: usize,
+    pinned_len: usize,
+    pinned_weight: usize,
+    listener: Option<Box<dyn EvictionListener<K, V> + Send + Sync>>,
+    default_ttl: Option<Duration>,
+    clock: Box<dyn Clock + Send + Sync>,
+    policy: P,
+    stats: R,
+}
+
+fn make_hash<Q>(hash_builder: &impl BuildHasher, q: &Q) -> u64
+where
+    Q: ?Sized + Hash,
+{
+    let mut state = hash_builder.build_hasher();
+    q.hash(&mut state);
+    state.finish()
 }
 
 impl<K, V> LruCache<K, V>
 where
     K: Eq + Hash,
 {
+    /// Creates a cache holding at most `capacity` entries, allocating room
+    /// for all of them up front.
+    ///
+    /// A cache with a zero capacity stores nothing: every pair inserted into
+    /// it is reported to the eviction listener as evicted for capacity right
+    /// away, and the methods that return a reference to the stored value,
+    /// like [`get_or_insert_with`](Self::get_or_insert_with) and
+    /// [`Entry::or_insert`], hand the pair back instead.
     pub fn new(capacity: usize) -> Self {
+        Self::with_policy(capacity, Lru)
+    }
+
+    /// Like [`new`](Self::new), but returns an error instead of creating a
+    /// cache that can never hold anything or panicking if room for
+    /// `capacity` entries cannot be allocated.
+    pub fn try_new(capacity: usize) -> Result<Self, CapacityError> {
+        if capacity == 0 {
+            return Err(CapacityError::Zero);
+        }
+
+        let mut cache = Self::from_parts(capacity, 0, None, Lru, DefaultHashBuilder::default());
+        cache
+            .graph
+            .nodes
+            .try_reserve(capacity)
+            .map_err(|_| CapacityError::TooLarge)?;
+        // The table is empty, so nothing is rehashed.
+        cache
+            .node_ids
+            .try_reserve(capacity, |_| 0)
+            .map_err(|_| CapacityError::TooLarge)?;
+
+        Ok(cache)
+    }
+
+    /// Creates a cache that never evicts for capacity but still tracks
+    /// recency, so that entries can be evicted by hand through
+    /// [`resize`](Self::resize), or looked up with
+    /// [`peek_lru`](Self::peek_lru) and [`remove`](Self::remove)d.
+    pub fn unbounded() -> Self {
+        Self::with_hasher(DefaultHashBuilder::default())
+    }
+
+    /// Creates a cache bounded by the total weight of its entries rather than
+    /// their number.
+    ///
+    /// Each entry is weighed once, when its value is inserted. Values mutated
+    /// in place through `get_mut`, `peek_mut`, entries or iterators keep the
+    /// weight they were inserted with.
+    pub fn with_weigher<W>(max_weight: usize, weigher: W) -> Self
+    where
+        W: Weigher<K, V> + Send + Sync + 'static,
+    {
+        Self::with_weigher_and_policy(max_weight, weigher, Lru)
+    }
+}
+
+impl<K, V, P> LruCache<K, V, P>
+where
+    K: Eq + Hash,
+    P: EvictionPolicy,
+{
+    /// Creates a cache that lets `policy` pick which entry to evict.
+    pub fn with_policy(capacity: usize, policy: P) -> Self {
+        Self::with_policy_and_hasher(capacity, policy, DefaultHashBuilder::default())
+    }
+
+    /// Creates a weighted cache, see [`with_weigher`](LruCache::with_weigher),
+    /// that lets `policy` pick which entry to evict.
+    pub fn with_weigher_and_policy<W>(max_weight: usize, weigher: W, policy: P) -> Self
+    where
+        W: Weigher<K, V> + Send + Sync + 'static,
+    {
+        Self::with_weigher_policy_and_hasher(
+            max_weight,
+            weigher,
+            policy,
+            DefaultHashBuilder::default(),
+        )
+    }
+}
+
+impl<K, V, S> LruCache<K, V, Lru, NoStats, S>
+where
+    K: Eq + Hash,
+    S: BuildHasher,
+{
+    /// Creates an [`unbounded`](LruCache::unbounded) cache that hashes keys
+    /// with `hash_builder`.
+    pub fn with_hasher(hash_builder: S) -> Self {
+        Self::from_parts(usize::MAX, 0, None, Lru, hash_builder)
+    }
+
+    /// Creates a cache holding at most `capacity` entries, like
+    /// [`new`](LruCache::new), that hashes keys with `hash_builder`.
+    pub fn with_capacity_and_hasher(capacity: usize, hash_builder: S) -> Self {
+        Self::from_parts(capacity, capacity, None, Lru, hash_builder)
+    }
+
+    /// Creates a weighted cache, like [`with_weigher`](LruCache::with_weigher),
+    /// that hashes keys with `hash_builder`.
+    pub fn with_weigher_and_hasher<W>(max_weight: usize, weigher: W, hash_builder: S) -> Self
+    where
+        W: Weigher<K, V> + Send + Sync + 'static,
+    {
+        Self::with_weigher_policy_and_hasher(max_weight, weigher, Lru, hash_builder)
+    }
+}
+
+impl<K, V, P, S> LruCache<K, V, P, NoStats, S>
+where
+    K: Eq + Hash,
+    P: EvictionPolicy,
+    S: BuildHasher,
+{
+    /// Creates a cache like [`with_policy`](LruCache::with_policy) that hashes
+    /// keys with `hash_builder`.
+    pub fn with_policy_and_hasher(capacity: usize, policy: P, hash_builder: S) -> Self {
+        Self::from_parts(capacity, capacity, None, policy, hash_builder)
+    }
+
+    /// Creates a cache like
+    /// [`with_weigher_and_policy`](LruCache::with_weigher_and_policy) that
+    /// hashes keys with `hash_builder`.
+    pub fn with_weigher_policy_and_hasher<W>(
+        max_weight: usize,
+        weigher: W,
+        policy: P,
+        hash_builder: S,
+    ) -> Self
+    where
+        W: Weigher<K, V> + Send + Sync + 'static,
+    {
+        Self::from_parts(max_weight, 0, Some(Box::new(weigher)), policy, hash_builder)
+    }
+
+    fn from_parts(
+        capacity: usize,
+        preallocate: usize,
+        weigher: Option<Box<dyn Weigher<K, V> + Send + Sync>>,
+        policy: P,
+        hash_builder: S,
+    ) -> Self {
         Self {
-            node_ids: HashMap::with_capacity(capacity),
-            graph: Graph::with_capacity(capacity),
-            id: 0,
+            node_ids: HashTable::with_capacity(preallocate),
+            graph: Graph::with_capacity(preallocate),
+            hash_builder,
+            capacity,
+            weigher,
+            total_weight: 0,
+            pinned_len: 0,
+            pinned_weight: 0,
+            listener: None,
+            default_ttl: None,
+            clock: Box::new(DefaultClock::default()),
+            policy,
+            stats: NoStats,
+        }
+    }
+}
+
+impl<K, V, P, R, S> LruCache<K, V, P, R, S>
+where
+    K: Eq + Hash,
+    P: EvictionPolicy,
+    R: StatsRecorder,
+    S: BuildHasher,
+{
+    /// Starts counting hits, misses, inserts and evictions, see
+    /// [`stats`](LruCache::stats). Counting is off by default and costs
+    /// nothing then.
+    pub fn with_stats(self) -> LruCache<K, V, P, StatsCounter, S> {
+        self.with_stats_recorder(StatsCounter::new())
+    }
+
+    /// Reports cache events to `recorder` instead of the current recorder.
+    pub fn with_stats_recorder<T>(self, recorder: T) -> LruCache<K, V, P, T, S>
+    where
+        T: StatsRecorder,
+    {
+        LruCache {
+            node_ids: self.node_ids,
+            graph: self.graph,
+            hash_builder: self.hash_builder,
+            capacity: self.capacity,
+            weigher: self.weigher,
+            total_weight: self.total_weight,
+            pinned_len: self.pinned_len,
+            pinned_weight: self.pinned_weight,
+            listener: self.listener,
+            default_ttl: self.default_ttl,
+            clock: self.clock,
+            policy: self.policy,
+            stats: recorder,
         }
     }
 
+    /// Returns the recorder cache events are reported to.
+    pub fn stats_recorder(&self) -> &R {
+        &self.stats
+    }
+
+    /// Registers a listener that is called for every entry evicted, removed
+    /// or replaced from now on, replacing any previous listener.
+    pub fn set_eviction_listener<L>(&mut self, listener: L)
+    where
+        L: EvictionListener<K, V> + Send + Sync + 'static,
+    {
+        self.listener = Some(Box::new(listener));
+    }
+
+    /// Sets the time-to-live given to entries inserted from now on without an
+    /// explicit one. `None`, the default, lets them live until evicted.
+    ///
+    /// Expired entries behave as absent on lookup and are removed when they
+    /// are looked up or by [`purge_expired`](Self::purge_expired). Until then
+    /// they still count towards [`len`](Self::len) and show up when
+    /// iterating.
+    ///
+    /// Without the `std` feature there is no default clock to measure time
+    /// with, and entries never expire until one is set with
+    /// [`set_clock`](Self::set_clock).
+    pub fn set_default_ttl(&mut self, ttl: Option<Duration>) {
+        self.default_ttl = ttl;
+    }
+
+    pub fn default_ttl(&self) -> Option<Duration> {
+        self.default_ttl
+    }
+
+    /// Replaces the clock used to expire entries. Set it before inserting
+    /// entries with a time-to-live, as their expiry times are read from the
+    /// clock in use when they were inserted.
+    ///
+    /// With the `std` feature the cache starts with a `MonotonicClock`.
+    /// Without it, time stands still until a clock is set here.
+    pub fn set_clock<C>(&mut self, clock: C)
+    where
+        C: Clock + Send + Sync + 'static,
+    {
+        self.clock = Box::new(clock);
+    }
+
     pub fn len(&self) -> usize {
         self.node_ids.len()
     }
 
+    pub fn is_empty(&self) -> bool {
+        self.node_ids.is_empty()
+    }
+
+    /// Returns the bound on [`total_weight`](Self::total_weight), which is the
+    /// maximum number of entries unless the cache was created with a weigher.
     pub fn capacity(&self) -> usize {
-        self.node_ids.capacity()
+        self.capacity
+    }
+
+    /// Returns the summed weight of all entries, which is the number of
+    /// entries unless the cache was created with a weigher.
+    pub fn total_weight(&self) -> usize {
+        self.total_weight
+    }
+
+    /// Returns how many entries are pinned, see [`pin`](Self::pin).
+    pub fn pinned_len(&self) -> usize {
+        self.pinned_len
+    }
+
+    /// Changes the capacity, evicting entries until the cache fits the new
+    /// bound. The evicted pairs are returned in eviction order.
+    ///
+    /// Pinned entries are kept even if they do not fit. New entries are then
+    /// rejected until enough of them are unpinned or removed.
+    pub fn resize(&mut self, capacity: usize) -> Vec<(K, V)> {
+        let mut evicted = Vec::new();
+
+        while self.total_weight > capacity {
+            match self.evict(None) {
+                Some(pair) => evicted.push(pair),
+                None => break,
+            }
+        }
+
+        self.capacity = capacity;
+        evicted
+    }
+
+    pub fn get<Q>(&mut self, k: &Q) -> Option<&V>
+    where
+        K: Borrow<Q>,
+        Q: ?Sized + Hash + Eq,
+    {
+        let node_id = self.lookup(make_hash(&self.hash_builder, k), k)?;
+        Some(&self.graph.get(node_id).unwrap().value)
+    }
+
+    pub fn get_mut<Q>(&mut self, k: &Q) -> Option<&mut V>
+    where
+        K: Borrow<Q>,
+        Q: ?Sized + Hash + Eq,
+    {
+        let node_id = self.lookup(make_hash(&self.hash_builder, k), k)?;
+        Some(&mut self.graph.get_mut(node_id).unwrap().value)
+    }
+
+    /// Gets the entry for a key for in-place manipulation. An occupied entry
+    /// is promoted to most recently used right away.
+    pub fn entry(&mut self, k: K) -> Entry<'_, K, V, P, R, S> {
+        let hash = make_hash(&self.hash_builder, &k);
+
+        match self.lookup(hash, &k) {
+            Some(node_id) => {
+                self.graph.move_to_back(node_id);
+                Entry::Occupied(OccupiedEntry::new(self, hash, node_id))
+            }
+            None => Entry::Vacant(VacantEntry::new(self, hash, k)),
+        }
+    }
+
+    /// Iterates over the entries from least to most recently used without
+    /// promoting them.
+    pub fn iter(&self) -> Iter<'_, K, V> {
+        Iter::new(&self.graph, self.len())
+    }
+
+    /// Iterates mutably over the entries from least to most recently used
+    /// without promoting them.
+    pub fn iter_mut(&mut self) -> IterMut<'_, K, V> {
+        let len = self.len();
+        IterMut::new(&mut self.graph, len)
+    }
+
+    pub fn keys(&self) -> Keys<'_, K, V> {
+        Keys { inner: self.iter() }
+    }
+
+    pub fn values(&self) -> Values<'_, K, V> {
+        Values { inner: self.iter() }
+    }
+
+    pub fn values_mut(&mut self) -> ValuesMut<'_, K, V> {
+        ValuesMut {
+            inner: self.iter_mut(),
+        }
+    }
+
+    /// Removes every entry, yielding them from least to most recently used.
+    /// Drained entries are not reported to the eviction listener.
+    pub fn drain(&mut self) -> Drain<'_, K, V> {
+        let len = self.len();
+        self.node_ids.clear();
+        self.total_weight = 0;
+        self.pinned_len = 0;
+        self.pinned_weight = 0;
+
+        let mut node_id = self.graph.head_id;
+
+        while let Some(id) = node_id {
+            self.policy.on_remove(id);
+            node_id = self.graph.node(id).unwrap().next_id;
+        }
+
+        let graph = Graph::with_capacity(self.graph.nodes.capacity());
+        Drain::new(IntoIter::new(mem::replace(&mut self.graph, graph), len))
+    }
+
+    /// Returns the value for a key without promoting it.
+    pub fn peek<Q>(&self, k: &Q) -> Option<&V>
+    where
+        K: Borrow<Q>,
+        Q: ?Sized + Hash + Eq,
+    {
+        let node_id = self.find_unexpired(make_hash(&self.hash_builder, k), k)?;
+        Some(&self.graph.node(node_id).unwrap().element.value)
+    }
+
+    /// Returns a mutable reference to the value for a key without promoting
+    /// it.
+    pub fn peek_mut<Q>(&mut self, k: &Q) -> Option<&mut V>
+    where
+        K: Borrow<Q>,
+        Q: ?Sized + Hash + Eq,
+    {
+        let node_id = self.find_unexpired(make_hash(&self.hash_builder, k), k)?;
+        Some(&mut self.graph.node_mut(node_id).unwrap().element.value)
     }
 
-    pub fn get<Q: ?Sized>(&mut self, k: &Q) -> Option<&V>
+    pub fn contains_key<Q>(&self, k: &Q) -> bool
     where
         K: Borrow<Q>,
-        Q: Hash + Eq,
+        Q: ?Sized + Hash + Eq,
     {
-        let node_id = *self.node_ids.get(k)?;
-        let (_k, v) = self.graph.get(node_id).unwrap();
-        Some(&v)
+        self.find_unexpired(make_hash(&self.hash_builder, k), k)
+            .is_some()
+    }
+
+    /// Returns the least recently used entry that has not expired.
+    ///
+    /// With the default [`Lru`] policy it is the next one to be evicted unless
+    /// it is pinned. Other policies pick their victims in their own order.
+    pub fn peek_lru(&self) -> Option<(&K, &V)> {
+        self.peek_live(self.graph.head_id, |node| node.next_id)
     }
 
-    pub fn remove<Q: ?Sized>(&mut self, k: &Q) -> Option<V>
+    /// Returns the most recently used entry that has not expired.
+    pub fn peek_mru(&self) -> Option<(&K, &V)> {
+        self.peek_live(self.graph.tail_id, |node| node.prev_id)
+    }
+
+    pub fn remove<Q>(&mut self, k: &Q) -> Option<V>
     where
         K: Borrow<Q>,
-        Q: Hash + Eq,
+        Q: ?Sized + Hash + Eq,
     {
-        let node_id = self.node_ids.remove(k)?;
-        let (_k, v) = self.graph.remove(node_id).unwrap();
+        let hash = make_hash(&self.hash_builder, k);
+        let node_id = self.find_live(hash, k)?;
+        let (_k, v) = self.remove_node(hash, node_id, EvictionReason::Removed);
         Some(v)
     }
 
-    pub fn insert(&mut self, k: K, v: V)
+    /// Pins the entry for a key so that it is never evicted to make room,
+    /// without promoting it. Returns whether the key was present.
+    ///
+    /// A pinned entry can still be removed, replaced or expire. Once pinned
+    /// entries take up the whole capacity, new pairs are rejected: they are
+    /// reported to the eviction listener as evicted for capacity right away,
+    /// like pairs heavier than the capacity, and handed back by
+    /// [`insert_or_reject`](Self::insert_or_reject), [`push`](Self::push) and the entry
+    /// API.
+    pub fn pin<Q>(&mut self, k: &Q) -> bool
     where
-        K: Clone,
+        K: Borrow<Q>,
+        Q: ?Sized + Hash + Eq,
     {
-        if let Some(&node_id) = self.node_ids.get(&k) {
-            self.graph.get(node_id).unwrap();
-            return;
+        self.set_pinned(k, true)
+    }
+
+    /// Makes a pinned entry evictable again, without promoting it. Returns
+    /// whether the key was present.
+    pub fn unpin<Q>(&mut self, k: &Q) -> bool
+    where
+        K: Borrow<Q>,
+        Q: ?Sized + Hash + Eq,
+    {
+        self.set_pinned(k, false)
+    }
+
+    /// Removes every expired entry, returning how many there were.
+    pub fn purge_expired(&mut self) -> usize {
+        let now = self.clock.now();
+        let mut expired = Vec::new();
+        let mut node_id = self.graph.head_id;
+
+        while let Some(id) = node_id {
+            let node = self.graph.node(id).unwrap();
+
+            if node.element.is_expired(now) {
+                expired.push(id);
+            }
+
+            node_id = node.next_id;
+        }
+
+        for &node_id in &expired {
+            let hash = make_hash(
+                &self.hash_builder,
+                &self.graph.node(node_id).unwrap().element.key,
+            );
+            self.remove_node(hash, node_id, EvictionReason::Expired);
         }
 
-        let node_id = self.id;
-        self.id += 1;
+        expired.len()
+    }
+
+    /// Inserts a key-value pair, promoting it to most recently used.
+    ///
+    /// If the key was already present its value is replaced and the old value
+    /// is returned. The entry expires after the default time-to-live, if any.
+    ///
+    /// A pair heavier than the whole capacity, or than what pinned entries
+    /// leave of it, is not stored: it is reported to the eviction listener as
+    /// evicted for capacity right away, and any previous entry for the key is
+    /// removed.
+    pub fn insert(&mut self, k: K, v: V) -> Option<V> {
+        let expires_at = self.expiry(self.default_ttl);
+        self.insert_expiring(k, v, expires_at).0
+    }
+
+    /// Inserts a key-value pair like [`insert`](Self::insert), returning the
+    /// previous value for the key, but hands the pair back along with that
+    /// value if the pair cannot be stored, for example because pinned entries
+    /// take up the whole capacity.
+    ///
+    /// The rejected pair is still reported to the eviction listener, and any
+    /// previous entry for the key is still removed.
+    pub fn insert_or_reject(&mut self, k: K, v: V) -> Result<Option<V>, Rejected<K, V>> {
+        let expires_at = self.expiry(self.default_ttl);
 
-        if self.len() == self.capacity() {
-            let (k, _v) = self.graph.pop_front().unwrap();
-            self.node_ids.remove(&k);
+        match self.insert_expiring(k, v, expires_at) {
+            (old_v, None) => Ok(old_v),
+            (old_v, Some(rejected)) => Err((old_v, rejected)),
         }
+    }
 
-        self.node_ids.insert(k.clone(), node_id);
-        self.graph.push_back(node_id, (k, v));
+    /// Inserts a key-value pair like [`insert`](Self::insert), but with its
+    /// own time-to-live instead of the default one.
+    ///
+    /// Without the `std` feature, the entry only expires if a clock was set
+    /// with [`set_clock`](Self::set_clock) beforehand.
+    pub fn insert_with_ttl(&mut self, k: K, v: V, ttl: Duration) -> Option<V> {
+        let expires_at = self.expiry(Some(ttl));
+        self.insert_expiring(k, v, expires_at).0
     }
-}
 
-#[cfg(test)]
-mod tests {
-    use super::*;
+    /// Inserts a key-value pair like [`insert`](Self::insert), but returns the
+    /// pair it displaced.
+    ///
+    /// That is the given key with the old value if the key was already
+    /// present, or the least recently used entry if it had to be evicted to
+    /// make room. If several entries had to be evicted, as can happen with a
+    /// weigher, only the first is returned. A new pair too heavy to be
+    /// stored at all is handed back itself.
+    ///
+    /// A new value too heavy for a key already present still displaces the
+    /// old one, which is returned, and is only reported to the eviction
+    /// listener like with [`insert`](Self::insert). Use
+    /// [`insert_or_reject`](Self::insert_or_reject) to get both back.
+    pub fn push(&mut self, k: K, v: V) -> Option<(K, V)> {
+        let hash = make_hash(&self.hash_builder, &k);
+        let expires_at = self.expiry(self.default_ttl);
 
-    #[test]
-    fn test_cache() {
-        let mut cache = LruCache::new(3);
+        if let Some(node_id) = self.find_live(hash, &k) {
+            self.policy.on_access(node_id);
 
-        cache.insert(&1, "aws");
-        assert_eq!(cache.get(&1), Some(&"aws"));
+            return match self.replace(hash, node_id, v, expires_at) {
+                Ok(old_v) | Err((old_v, _)) => Some((k, old_v)),
+            };
+        }
 
-        cache.insert(&2, "gcp");
-        cache.insert(&3, "azure");
-        assert_eq!(cache.get(&3), Some(&"azure"));
+        match self.push_new(hash, k, v, expires_at) {
+            Ok((_node_id, evicted)) => evicted,
+            Err(rejected) => Some(rejected),
+        }
+    }
 
-        cache.insert(&4, "vmware");
-        assert_eq!(cache.get(&2), Some(&"gcp"));
-        assert_eq!(cache.get(&1), None);
+    /// Inserts a key-value pair only if the key is not present yet.
+    ///
+    /// An existing entry keeps its value but is still promoted. Returns whether
+    /// the pair was inserted.
+    pub fn insert_if_absent(&mut self, k: K, v: V) -> bool {
+        let hash = make_hash(&self.hash_builder, &k);
+
+        if let Some(node_id) = self.find_live(hash, &k) {
+            self.graph.get(node_id).unwrap();
+            self.policy.on_access(node_id);
+            return false;
+        }
+
+        let expires_at = self.expiry(self.default_ttl);
+        self.push_new(hash, k, v, expires_at).is_ok()
+    }
+
+    /// Returns the value for a key, promoting it, or inserts the value
+    /// computed by `f` if the key is absent. The key is hashed and looked up
+    /// only once either way.
+    ///
+    /// A computed value that cannot be stored, as in a zero-capacity cache, is
+    /// handed back with its key instead, after being reported to the eviction
+    /// listener like [`insert`](Self::insert) does.
+    pub fn get_or_insert_with<F>(&mut self, k: K, f: F) -> Result<&V, (K, V)>
+    where
+        F: FnOnce() -> V,
+    {
+        match self.try_get_or_insert_with_key(k, |_k| Ok::<_, Infallible>(f())) {
+            Ok(inserted) => inserted,
+            Err(never) => match never {},
+        }
+    }
+
+    /// Like [`get_or_insert_with`](Self::get_or_insert_with), but `f` may
+    /// fail, in which case nothing is inserted and its error is returned.
+    pub fn try_get_or_insert_with<F, E>(&mut self, k: K, f: F) -> Result<Result<&V, (K, V)>, E>
+    where
+        F: FnOnce() -> Result<V, E>,
+    {
+        self.try_get_or_insert_with_key(k, |_k| f())
+    }
+
+    pub(crate) fn try_get_or_insert_with_key<F, E>(
+        &mut self,
+        k: K,
+        f: F,
+    ) -> Result<Result<&V, (K, V)>, E>
+    where
+        F: FnOnce(&K) -> Result<V, E>,
+    {
+        let hash = make_hash(&self.hash_builder, &k);
+
+        let node_id = match self.lookup(hash, &k) {
+            Some(node_id) => {
+                self.graph.move_to_back(node_id);
+                node_id
+            }
+            None => {
+                let v = f(&k)?;
+                let expires_at = self.expiry(self.default_ttl);
+
+                match self.push_new(hash, k, v, expires_at) {
+                    Ok((node_id, _evicted)) => node_id,
+                    Err(rejected) => return Ok(Err(rejected)),
+                }
+            }
+        };
+
+        Ok(Ok(&self.graph.node(node_id).unwrap().element.value))
+    }
+
+    /// Returns the previous value for the key, and the pair itself if it was
+    /// rejected.
+    fn insert_expiring(
+        &mut self,
+        k: K,
+        v: V,
+        expires_at: Option<Duration>,
+    ) -> (Option<V>, Option<(K, V)>) {
+        let hash = make_hash(&self.hash_builder, &k);
+
+        if let Some(node_id) = self.find_live(hash, &k) {
+            self.policy.on_access(node_id);
+
+            return match self.replace(hash, node_id, v, expires_at) {
+                Ok(old_v) => (Some(old_v), None),
+                Err((old_v, rejected)) => (Some(old_v), Some(rejected)),
+            };
+        }
+
+        (None, self.push_new(hash, k, v, expires_at).err())
+    }
+
+    fn find<Q>(&self, hash: u64, k: &Q) -> Option<NodeId>
+    where
+        K: Borrow<Q>,
+        Q: ?Sized + Eq,
+    {
+        let graph = &self.graph;
+        let node_id = self.node_ids.find(hash, |&node_id| {
+            graph.node(node_id).unwrap().element.key.borrow() == k
+        })?;
+
+        Some(*node_id)
+    }
+
+    fn find_unexpired<Q>(&self, hash: u64, k: &Q) -> Option<NodeId>
+    where
+        K: Borrow<Q>,
+        Q: ?Sized + Eq,
+    {
+        let node_id = self.find(hash, k)?;
+        (!self.is_expired(node_id)).then_some(node_id)
+    }
+
+    /// Like `find`, but removes the entry if it has expired.
+    fn find_live<Q>(&mut self, hash: u64, k: &Q) -> Option<NodeId>
+    where
+        K: Borrow<Q>,
+        Q: ?Sized + Eq,
+    {
+        let node_id = self.find(hash, k)?;
+
+        if self.is_expired(node_id) {
+            self.remove_node(hash, node_id, EvictionReason::Expired);
+            return None;
+        }
+
+        Some(node_id)
+    }
+
+    /// Like `find_live`, but also reports the hit or miss to the policy.
+    fn lookup<Q>(&mut self, hash: u64, k: &Q) -> Option<NodeId>
+    where
+        K: Borrow<Q>,
+        Q: ?Sized + Eq,
+    {
+        match self.find_live(hash, k) {
+            Some(node_id) => {
+                self.stats.record_hit();
+                self.policy.on_access(node_id);
+                Some(node_id)
+            }
+            None => {
+                self.stats.record_miss();
+                self.policy.on_miss(hash);
+                None
+            }
+        }
+    }
+
+    fn set_pinned<Q>(&mut self, k: &Q, pinned: bool) -> bool
+    where
+        K: Borrow<Q>,
+        Q: ?Sized + Hash + Eq,
+    {
+        let Some(node_id) = self.find_live(make_hash(&self.hash_builder, k), k) else {
+            return false;
+        };
+
+        let item = &mut self.graph.node_mut(node_id).unwrap().element;
+
+        if item.pinned != pinned {
+            item.pinned = pinned;
+
+            if pinned {
+                self.pinned_len += 1;
+                self.pinned_weight += item.weight;
+            } else {
+                self.pinned_len -= 1;
+                self.pinned_weight -= item.weight;
+            }
+        }
+
+        true
+    }
+
+    /// Returns the first entry that has not expired, walking from `node_id`.
+    fn peek_live(
+        &self,
+        mut node_id: Option<NodeId>,
+        step: impl Fn(&Node<Item<K, V>>) -> Option<NodeId>,
+    ) -> Option<(&K, &V)> {
+        let now = self.clock.now();
+
+        while let Some(node) = self.graph.node(node_id?) {
+            if !node.element.is_expired(now) {
+                return Some((&node.element.key, &node.element.value));
+            }
+
+            node_id = step(node);
+        }
+
+        None
+    }
+
+    fn is_expired(&self, node_id: NodeId) -> bool {
+        let item = &self.graph.node(node_id).unwrap().element;
+        item.expires_at.is_some() && item.is_expired(self.clock.now())
+    }
+
+    fn expiry(&self, ttl: Option<Duration>) -> Option<Duration> {
+        let ttl = ttl?;
+        self.clock.now().checked_add(ttl)
+    }
+
+    fn weigh(&self, k: &K, v: &V) -> usize {
+        self.weigher
+            .as_ref()
+            .map_or(1, |weigher| weigher.weigh(k, v))
+    }
+
+    /// Returns whether an entry of `weight` fits next to `pinned_weight` worth
+    /// of pinned entries.
+    fn fits(&self, weight: usize, pinned_weight: usize) -> bool {
+        matches!(weight.checked_add(pinned_weight), Some(total) if total <= self.capacity)
+    }
+
+    /// Replaces the value of an entry and promotes it, returning the old value.
+    /// The caller reports the access to the policy, as it looked the entry up.
+    ///
+    /// If the new value is too heavy for the cache, the entry is removed
+    /// instead and its key is handed back with the new value.
+    fn replace(
+        &mut self,
+        hash: u64,
+        node_id: NodeId,
+        v: V,
+        expires_at: Option<Duration>,
+    ) -> Replaced<K, V> {
+        let item = &self.graph.node(node_id).unwrap().element;
+        let weight = self.weigh(&item.key, &v);
+        let pinned = item.pinned;
+        let others_pinned = self.pinned_weight - if pinned { item.weight } else { 0 };
+
+        if !self.fits(weight, others_pinned) {
+            let (k, old_v) = self.remove_node(hash, node_id, EvictionReason::Replaced);
+            self.notify(&k, &v, EvictionReason::Capacity);
+            return Err((old_v, (k, v)));
+        }
+
+        let item = self.graph.get_mut(node_id).unwrap();
+        let old_v = mem::replace(&mut item.value, v);
+        let old_weight = mem::replace(&mut item.weight, weight);
+        item.expires_at = expires_at;
+
+        if pinned {
+            self.pinned_weight = others_pinned + weight;
+        }
+
+        self.stats.record_eviction(EvictionReason::Replaced);
+
+        if let Some(listener) = &mut self.listener {
+            listener.on_evict(&item.key, &old_v, EvictionReason::Replaced);
+        }
+
+        // The entry fits next to the pinned ones, so evicting the others makes
+        // room. Comparing the others against what the entry leaves of the
+        // capacity cannot overflow.
+        while self.total_weight - old_weight > self.capacity - weight {
+            self.evict(Some(node_id));
+        }
+
+        self.total_weight = self.total_weight - old_weight + weight;
+
+        Ok(old_v)
+    }
+
+    /// Links a new entry at the back, evicting from the front until it fits.
+    /// Returns the first evicted pair, or the new pair itself if it does not
+    /// fit next to the pinned entries.
+    fn push_new(&mut self, hash: u64, k: K, v: V, expires_at: Option<Duration>) -> Pushed<K, V> {
+        let weight = self.weigh(&k, &v);
+
+        // Expired entries are never picked as victims, so pinned ones could
+        // otherwise keep taking up room until they are looked up.
+        if weight <= self.capacity && !self.fits(weight, self.pinned_weight) {
+            self.purge_expired();
+        }
+
+        if !self.fits(weight, self.pinned_weight) {
+            self.notify(&k, &v, EvictionReason::Capacity);
+            return Err((k, v));
+        }
+
+        let mut evicted = None;
+
+        while self.total_weight > self.capacity - weight {
+            let pair = self.evict(None).unwrap();
+            evicted.get_or_insert(pair);
+        }
+
+        self.total_weight += weight;
+        let node_id = self.graph.push_back(Item {
+            key: k,
+            value: v,
+            expires_at,
+            weight,
+            pinned: false,
+        });
+        self.policy.on_insert(node_id, hash);
+        self.stats.record_insert();
+        let (graph, hash_builder) = (&self.graph, &self.hash_builder);
+        self.node_ids.insert_unique(hash, node_id, |&node_id| {
+            make_hash(hash_builder, &graph.node(node_id).unwrap().element.key)
+        });
+
+        Ok((node_id, evicted))
+    }
+
+    /// Evicts the entry chosen by the policy, or the least recently used one
+    /// if it has no preference or chose a vacant slot, a pinned entry or
+    /// `spare`. Returns
+    /// `None` if every entry is pinned or spared.
+    fn evict(&mut self, spare: Option<NodeId>) -> Option<(K, V)> {
+        let evictable = |node_id| {
+            Some(node_id) != spare
+                && matches!(self.graph.node(node_id), Some(node) if !node.element.pinned)
+        };
+
+        let node_id = match self.policy.victim() {
+            Some(node_id) if evictable(node_id) => node_id,
+            _ => {
+                let mut node_id = self.graph.head_id?;
+
+                while !evictable(node_id) {
+                    node_id = self.graph.node(node_id).unwrap().next_id?;
+                }
+
+                node_id
+            }
+        };
+
+        let hash = make_hash(&self.hash_builder, &self.graph.node(node_id)?.element.key);
+        Some(self.remove_node(hash, node_id, EvictionReason::Capacity))
+    }
+
+    fn remove_node(&mut self, hash: u64, node_id: NodeId, reason: EvictionReason) -> (K, V) {
+        self.unindex(hash, node_id);
+        let item = self.graph.remove(node_id).unwrap();
+        self.policy.on_remove(node_id);
+        self.total_weight -= item.weight;
+
+        if item.pinned {
+            self.pinned_len -= 1;
+            self.pinned_weight -= item.weight;
+        }
+
+        self.notify(&item.key, &item.value, reason);
+        (item.key, item.value)
+    }
+
+    fn unindex(&mut self, hash: u64, node_id: NodeId) {
+        if let Ok(entry) = self.node_ids.find_entry(hash, |&id| id == node_id) {
+            entry.remove();
+        }
+    }
+
+    fn notify(&mut self, k: &K, v: &V, reason: EvictionReason) {
+        self.stats.record_eviction(reason);
+
+        if let Some(listener) = &mut self.listener {
+            listener.on_evict(k, v, reason);
+        }
+    }
+}
+
+impl<K, V, P, S> LruCache<K, V, P, StatsCounter, S> {
+    /// Returns the counts recorded since the cache started counting, see
+    /// [`with_stats`](LruCache::with_stats), or since the last reset.
+    pub fn stats(&self) -> CacheStats {
+        self.stats.snapshot(self.node_ids.len(), self.total_weight)
+    }
+
+    pub fn reset_stats(&mut self) {
+        self.stats = StatsCounter::new();
+    }
+}
+
+impl<K, V> Item<K, V> {
+    fn is_expired(&self, now: Duration) -> bool {
+        self.expires_at
+            .map_or(false, |expires_at| now >= expires_at)
+    }
+}
+
+impl<'a, K, V, P, R, S> IntoIterator for &'a LruCache<K, V, P, R, S>
+where
+    K: Eq + Hash,
+    P: EvictionPolicy,
+    R: StatsRecorder,
+    S: BuildHasher,
+{
+    type Item = (&'a K, &'a V);
+    type IntoIter = Iter<'a, K, V>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+impl<'a, K, V, P, R, S> IntoIterator for &'a mut LruCache<K, V, P, R, S>
+where
+    K: Eq + Hash,
+    P: EvictionPolicy,
+    R: StatsRecorder,
+    S: BuildHasher,
+{
+    type Item = (&'a K, &'a mut V);
+    type IntoIter = IterMut<'a, K, V>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter_mut()
+    }
+}
+
+impl<K, V, P, R, S> IntoIterator for LruCache<K, V, P, R, S> {
+    type Item = (K, V);
+    type IntoIter = IntoIter<K, V>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        IntoIter::new(self.graph, self.node_ids.len())
+    }
+}
+
+impl<K, V, P, R, S> Cache<K, V> for LruCache<K, V, P, R, S>
+where
+    K: Eq + Hash,
+    P: EvictionPolicy,
+    R: StatsRecorder,
+    S: BuildHasher,
+{
+    fn get(&mut self, k: &K) -> Option<&V> {
+        LruCache::get(self, k)
+    }
+
+    fn insert(&mut self, k: K, v: V) -> Option<V> {
+        LruCache::insert(self, k, v)
+    }
+
+    fn remove(&mut self, k: &K) -> Option<V> {
+        LruCache::remove(self, k)
+    }
+
+    fn len(&self) -> usize {
+        LruCache::len(self)
+    }
+
+    fn capacity(&self) -> usize {
+        LruCache::capacity(self)
+    }
+}
+
+impl<K, V, P, R, S> fmt::Debug for LruCache<K, V, P, R, S>
+where
+    K: fmt::Debug,
+    V: fmt::Debug,
+    P: fmt::Debug,
+    R: fmt::Debug,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("LruCache")
+            .field("graph", &self.graph)
+            .field("capacity", &self.capacity)
+            .field("total_weight", &self.total_weight)
+            .field("policy", &self.policy)
+            .field("stats", &self.stats)
+            .finish_non_exhaustive()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        borrow::ToOwned,
+        iter,
+        string::String,
+        sync::{Arc, Mutex},
+    };
+
+    use super::*;
+
+    type Evictions<K, V> = Arc<Mutex<Vec<(K, V, EvictionReason)>>>;
+
+    fn order<K: Clone, V, P, R, S>(cache: &LruCache<K, V, P, R, S>) -> Vec<K> {
+        let mut keys = Vec::new();
+        let mut node_id = cache.graph.head_id;
+
+        while let Some(id) = node_id {
+            let node = cache.graph.node(id).unwrap();
+            keys.push(node.element.key.clone());
+            node_id = node.next_id;
+        }
+
+        keys
+    }
+
+    /// Sets a listener on `cache` that records every eviction.
+    fn record_evictions<K, V, P, R, S>(cache: &mut LruCache<K, V, P, R, S>) -> Evictions<K, V>
+    where
+        K: Clone + Eq + Hash + Send + 'static,
+        V: Clone + Send + 'static,
+        P: EvictionPolicy,
+        R: StatsRecorder,
+        S: BuildHasher,
+    {
+        let evictions = Evictions::default();
+        let log = evictions.clone();
+        cache.set_eviction_listener(move |k: &K, v: &V, reason| {
+            log.lock().unwrap().push((k.clone(), v.clone(), reason));
+        });
+        evictions
+    }
+
+    /// An endless xorshift sequence starting after `state`, for randomized
+    /// tests.
+    pub(crate) fn xorshift(mut state: u32) -> impl Iterator<Item = u32> {
+        iter::repeat_with(move || {
+            state ^= state << 13;
+            state ^= state >> 17;
+            state ^= state << 5;
+            state
+        })
+    }
+
+    #[test]
+    fn test_cache() {
+        let mut cache = LruCache::new(3);
+
+        cache.insert(&1, "aws");
+        assert_eq!(cache.get(&1), Some(&"aws"));
+
+        cache.insert(&2, "gcp");
+        cache.insert(&3, "azure");
+        assert_eq!(cache.get(&3), Some(&"azure"));
+
+        cache.insert(&4, "vmware");
+        assert_eq!(cache.get(&2), Some(&"gcp"));
+        assert_eq!(cache.get(&1), None);
 
         cache.insert(&5, "val");
         assert_eq!(cache.get(&5), Some(&"val"));
@@ -190,4 +1451,1123 @@ mod tests {
         assert_eq!(cache.get(&2), Some(&"gcp"));
         assert_eq!(cache.get(&1), None);
     }
+
+    #[test]
+    fn test_graph_reuses_node_ids() {
+        let mut graph = Graph::with_capacity(2);
+
+        let a = graph.push_back("a");
+        let b = graph.push_back("b");
+        assert_eq!(graph.pop_front(), Some("a"));
+
+        let c = graph.push_back("c");
+        assert_eq!(c, a);
+        assert_eq!(graph.nodes.len(), 2);
+
+        assert_eq!(graph.get(b), Some(&"b"));
+        assert_eq!(graph.pop_front(), Some("c"));
+        assert_eq!(graph.pop_front(), Some("b"));
+        assert_eq!(graph.pop_front(), None);
+        assert_eq!(graph.remove(b), None);
+    }
+
+    #[test]
+    fn test_recency_order() {
+        let mut cache = LruCache::new(3);
+
+        cache.insert(1, "a");
+        cache.insert(2, "b");
+        cache.insert(3, "c");
+        assert_eq!(order(&cache), [1, 2, 3]);
+
+        cache.get(&1);
+        assert_eq!(order(&cache), [2, 3, 1]);
+
+        cache.get(&3);
+        assert_eq!(order(&cache), [2, 1, 3]);
+
+        cache.get(&3);
+        assert_eq!(order(&cache), [2, 1, 3]);
+
+        cache.insert(2, "b");
+        assert_eq!(order(&cache), [1, 3, 2]);
+
+        cache.get(&4);
+        assert_eq!(order(&cache), [1, 3, 2]);
+    }
+
+    #[test]
+    fn test_remove_relinks_neighbours() {
+        let mut cache = LruCache::new(4);
+
+        for k in 1..=4 {
+            cache.insert(k, k);
+        }
+
+        assert_eq!(cache.remove(&2), Some(2));
+        assert_eq!(order(&cache), [1, 3, 4]);
+
+        assert_eq!(cache.remove(&1), Some(1));
+        assert_eq!(order(&cache), [3, 4]);
+
+        assert_eq!(cache.remove(&4), Some(4));
+        assert_eq!(order(&cache), [3]);
+
+        assert_eq!(cache.remove(&3), Some(3));
+        assert!(order(&cache).is_empty());
+        assert!(cache.is_empty());
+
+        cache.insert(5, 5);
+        cache.insert(6, 6);
+        assert_eq!(order(&cache), [5, 6]);
+    }
+
+    #[test]
+    fn test_matches_model() {
+        let mut cache = LruCache::new(8);
+        let capacity = cache.capacity();
+        let mut model: Vec<u32> = Vec::new();
+
+        for seed in xorshift(0x9e37_79b9).take(10_000) {
+            let k = seed % 32;
+
+            match seed % 3 {
+                0 => {
+                    if let Some(i) = model.iter().position(|&m| m == k) {
+                        model.remove(i);
+                    } else if model.len() == capacity {
+                        model.remove(0);
+                    }
+
+                    model.push(k);
+                    cache.insert(k, ());
+                }
+                1 => {
+                    let hit = model.iter().position(|&m| m == k).map(|i| model.remove(i));
+                    model.extend(hit);
+                    assert_eq!(cache.get(&k).is_some(), hit.is_some());
+                }
+                _ => {
+                    let hit = model.iter().position(|&m| m == k).map(|i| model.remove(i));
+                    assert_eq!(cache.remove(&k).is_some(), hit.is_some());
+                }
+            }
+
+            assert_eq!(order(&cache), model);
+        }
+    }
+
+    #[test]
+    fn test_insert_replaces_value() {
+        let mut cache = LruCache::new(3);
+
+        assert_eq!(cache.insert(1, "a"), None);
+        assert_eq!(cache.insert(2, "b"), None);
+        assert_eq!(cache.insert(1, "c"), Some("a"));
+        assert_eq!(order(&cache), [2, 1]);
+        assert_eq!(cache.get(&1), Some(&"c"));
+        assert_eq!(cache.len(), 2);
+    }
+
+    #[test]
+    fn test_insert_if_absent_keeps_first() {
+        let mut cache = LruCache::new(3);
+
+        assert!(cache.insert_if_absent(1, "a"));
+        assert!(cache.insert_if_absent(2, "b"));
+        assert!(!cache.insert_if_absent(1, "c"));
+        assert_eq!(order(&cache), [2, 1]);
+        assert_eq!(cache.get(&1), Some(&"a"));
+    }
+
+    #[test]
+    fn test_send_sync() {
+        fn assert_send_sync<T: Send + Sync>(_: &T) {}
+
+        let mut cache = LruCache::with_weigher(4, |_k: &u32, v: &String| v.len());
+        cache.set_eviction_listener(|_k: &u32, _v: &String, _reason| {});
+        cache.set_clock(ManualClock::new());
+        assert_send_sync(&cache);
+        assert_send_sync(&LruCache::<u32, u32>::new(1));
+    }
+
+    #[test]
+    fn test_zero_capacity() {
+        let mut cache = LruCache::new(0);
+        let evicted = record_evictions(&mut cache);
+
+        assert_eq!(cache.insert(1, 1), None);
+        assert_eq!(cache.push(2, 2), Some((2, 2)));
+        assert!(!cache.insert_if_absent(3, 3));
+        assert_eq!(cache.entry(4).or_insert(4), Err((4, 4)));
+        assert_eq!(cache.get_or_insert_with(5, || 5), Err((5, 5)));
+        assert_eq!(
+            cache.try_get_or_insert_with(6, || Ok::<_, ()>(6)),
+            Ok(Err((6, 6)))
+        );
+        assert_eq!(cache.get(&1), None);
+        assert!(cache.is_empty());
+        assert_eq!(
+            *evicted.lock().unwrap(),
+            [
+                (1, 1, EvictionReason::Capacity),
+                (2, 2, EvictionReason::Capacity),
+                (3, 3, EvictionReason::Capacity),
+                (4, 4, EvictionReason::Capacity),
+                (5, 5, EvictionReason::Capacity),
+                (6, 6, EvictionReason::Capacity),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_unbounded() {
+        let mut cache = LruCache::unbounded();
+
+        for k in 0..10_000 {
+            cache.insert(k, k);
+        }
+
+        cache.get(&0);
+        assert_eq!(cache.len(), 10_000);
+        assert_eq!(cache.peek_lru(), Some((&1, &1)));
+        assert_eq!(cache.peek_mru(), Some((&0, &0)));
+        assert_eq!(
+            cache.resize(2),
+            (1..9_999).map(|k| (k, k)).collect::<Vec<_>>()
+        );
+        assert_eq!(order(&cache), [9_999, 0]);
+    }
+
+    #[test]
+    fn test_try_new() {
+        assert_eq!(
+            LruCache::<u32, u32>::try_new(0).unwrap_err(),
+            CapacityError::Zero
+        );
+        assert_eq!(
+            LruCache::<u32, u32>::try_new(usize::MAX).unwrap_err(),
+            CapacityError::TooLarge
+        );
+
+        let mut cache = LruCache::try_new(1).unwrap();
+        cache.insert(1, 1);
+        cache.insert(2, 2);
+        assert_eq!(order(&cache), [2]);
+    }
+
+    #[test]
+    fn test_exact_capacity() {
+        let mut cache = LruCache::new(3);
+
+        for k in 1..=4 {
+            cache.insert(k, k);
+        }
+
+        assert_eq!(cache.capacity(), 3);
+        assert_eq!(cache.len(), 3);
+        assert_eq!(order(&cache), [2, 3, 4]);
+    }
+
+    #[test]
+    fn test_resize() {
+        let mut cache = LruCache::new(4);
+
+        for k in 1..=4 {
+            cache.insert(k, k * 10);
+        }
+
+        cache.get(&1);
+        assert_eq!(cache.resize(2), [(2, 20), (3, 30)]);
+        assert_eq!(cache.capacity(), 2);
+        assert_eq!(order(&cache), [4, 1]);
+
+        assert_eq!(cache.resize(3), []);
+        cache.insert(5, 50);
+        assert_eq!(order(&cache), [4, 1, 5]);
+
+        cache.insert(6, 60);
+        assert_eq!(order(&cache), [1, 5, 6]);
+    }
+
+    #[test]
+    fn test_push_returns_displaced_pair() {
+        let mut cache = LruCache::new(2);
+
+        assert_eq!(cache.push(1, "a"), None);
+        assert_eq!(cache.push(2, "b"), None);
+        assert_eq!(cache.push(1, "c"), Some((1, "a")));
+        assert_eq!(cache.push(3, "d"), Some((2, "b")));
+        assert_eq!(order(&cache), [1, 3]);
+    }
+
+    #[test]
+    fn test_eviction_listener() {
+        let mut cache = LruCache::new(2);
+        let events = record_evictions(&mut cache);
+
+        cache.insert(1, "a");
+        cache.insert(2, "b");
+        cache.insert(1, "c");
+        cache.insert(3, "d");
+        cache.remove(&1);
+        cache.remove(&1);
+        cache.insert(4, "e");
+        cache.resize(1);
+
+        assert_eq!(
+            *events.lock().unwrap(),
+            [
+                (1, "a", EvictionReason::Replaced),
+                (2, "b", EvictionReason::Capacity),
+                (1, "c", EvictionReason::Removed),
+                (3, "d", EvictionReason::Capacity),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_keys_without_clone() {
+        #[derive(PartialEq, Eq, Hash)]
+        struct Key(String);
+
+        let mut cache = LruCache::new(2);
+
+        cache.insert(Key("a".to_owned()), 1);
+        cache.insert(Key("b".to_owned()), 2);
+        cache.insert(Key("c".to_owned()), 3);
+
+        assert_eq!(cache.get(&Key("a".to_owned())), None);
+        assert_eq!(cache.get(&Key("b".to_owned())), Some(&2));
+        assert_eq!(cache.remove(&Key("c".to_owned())), Some(3));
+        assert_eq!(cache.len(), 1);
+    }
+
+    #[test]
+    fn test_peek_keeps_order() {
+        let mut cache = LruCache::new(3);
+
+        assert_eq!(cache.peek_lru(), None);
+        assert_eq!(cache.peek_mru(), None);
+
+        for k in 1..=3 {
+            cache.insert(k, k * 10);
+        }
+
+        assert_eq!(cache.peek(&1), Some(&10));
+        assert_eq!(cache.peek(&4), None);
+        assert!(cache.contains_key(&2));
+        assert!(!cache.contains_key(&4));
+
+        *cache.peek_mut(&1).unwrap() += 1;
+        assert_eq!(cache.peek(&1), Some(&11));
+
+        assert_eq!(cache.peek_lru(), Some((&1, &11)));
+        assert_eq!(cache.peek_mru(), Some((&3, &30)));
+        assert_eq!(order(&cache), [1, 2, 3]);
+
+        cache.insert(4, 40);
+        assert!(!cache.contains_key(&1));
+    }
+
+    #[test]
+    fn test_get_mut_promotes() {
+        let mut cache = LruCache::new(3);
+
+        for k in 1..=3 {
+            cache.insert(k, k * 10);
+        }
+
+        *cache.get_mut(&1).unwrap() += 1;
+        assert_eq!(cache.get_mut(&4), None);
+        assert_eq!(order(&cache), [2, 3, 1]);
+        assert_eq!(cache.peek(&1), Some(&11));
+    }
+
+    #[test]
+    fn test_entry() {
+        let mut cache = LruCache::new(3);
+
+        *cache.entry(1).or_insert(10).unwrap() += 1;
+        cache.entry(2).or_insert_with(|| 20).unwrap();
+        cache.entry(3).or_insert(30).unwrap();
+        assert_eq!(order(&cache), [1, 2, 3]);
+
+        cache.entry(1).and_modify(|v| *v += 1).or_insert(0).unwrap();
+        assert_eq!(order(&cache), [2, 3, 1]);
+        assert_eq!(cache.peek(&1), Some(&12));
+
+        assert_eq!(
+            cache.entry(4).and_modify(|v| *v += 1).or_insert(40),
+            Ok(&mut 40)
+        );
+        assert_eq!(order(&cache), [3, 1, 4]);
+
+        match cache.entry(1) {
+            Entry::Occupied(entry) => {
+                assert_eq!(entry.key(), &1);
+                assert_eq!(entry.remove(), 12);
+            }
+            Entry::Vacant(_) => unreachable!(),
+        }
+
+        assert_eq!(order(&cache), [3, 4]);
+
+        match cache.entry(5) {
+            Entry::Occupied(_) => unreachable!(),
+            Entry::Vacant(entry) => {
+                assert_eq!(entry.key(), &5);
+                assert_eq!(entry.insert(50), Ok(&mut 50));
+            }
+        }
+
+        assert_eq!(order(&cache), [3, 4, 5]);
+        assert_eq!(cache.len(), 3);
+    }
+
+    #[test]
+    fn test_iter() {
+        let mut cache = LruCache::new(3);
+
+        for k in 1..=3 {
+            cache.insert(k, k * 10);
+        }
+
+        cache.get(&1);
+
+        assert_eq!(
+            cache.iter().collect::<Vec<_>>(),
+            [(&2, &20), (&3, &30), (&1, &10)]
+        );
+        assert_eq!(
+            cache.iter().rev().collect::<Vec<_>>(),
+            [(&1, &10), (&3, &30), (&2, &20)]
+        );
+        assert_eq!(cache.keys().copied().collect::<Vec<_>>(), [2, 3, 1]);
+        assert_eq!(
+            cache.values().rev().copied().collect::<Vec<_>>(),
+            [10, 30, 20]
+        );
+
+        let mut iter = cache.iter();
+        assert_eq!(iter.len(), 3);
+        assert_eq!(iter.next(), Some((&2, &20)));
+        assert_eq!(iter.next_back(), Some((&1, &10)));
+        assert_eq!(iter.len(), 1);
+        assert_eq!(iter.next(), Some((&3, &30)));
+        assert_eq!(iter.next_back(), None);
+        assert_eq!(iter.next(), None);
+
+        assert_eq!(order(&cache), [2, 3, 1]);
+    }
+
+    #[test]
+    fn test_iter_mut() {
+        let mut cache = LruCache::new(3);
+
+        for k in 1..=3 {
+            cache.insert(k, k * 10);
+        }
+
+        for (k, v) in &mut cache {
+            *v += k;
+        }
+
+        let mut iter = cache.values_mut();
+        *iter.next_back().unwrap() += 100;
+        *iter.next().unwrap() += 100;
+
+        assert_eq!(cache.values().copied().collect::<Vec<_>>(), [111, 22, 133]);
+        assert_eq!(order(&cache), [1, 2, 3]);
+    }
+
+    #[test]
+    fn test_drain_and_into_iter() {
+        let mut cache = LruCache::new(3);
+
+        for k in 1..=3 {
+            cache.insert(k, k * 10);
+        }
+
+        let mut drain = cache.drain();
+        assert_eq!(drain.next(), Some((1, 10)));
+        assert_eq!(drain.next_back(), Some((3, 30)));
+        drop(drain);
+
+        assert!(cache.is_empty());
+        assert!(cache.graph.nodes.capacity() >= 3);
+        assert_eq!(cache.get(&2), None);
+
+        for k in 4..=6 {
+            cache.insert(k, k * 10);
+        }
+
+        assert_eq!(order(&cache), [4, 5, 6]);
+        assert_eq!(
+            cache.into_iter().rev().collect::<Vec<_>>(),
+            [(6, 60), (5, 50), (4, 40)]
+        );
+    }
+
+    #[test]
+    fn test_peek_ends_skip_expired() {
+        let clock = ManualClock::new();
+        let mut cache = LruCache::new(4);
+        cache.set_clock(clock.clone());
+        cache.insert_with_ttl(1, 1, Duration::from_secs(1));
+        cache.insert_with_ttl(2, 2, Duration::from_secs(2));
+        cache.insert_with_ttl(3, 3, Duration::from_secs(2));
+        cache.insert_with_ttl(4, 4, Duration::from_secs(1));
+        clock.advance(Duration::from_secs(1));
+
+        assert_eq!(cache.peek(&1), None);
+        assert_eq!(cache.peek_lru(), Some((&2, &2)));
+        assert_eq!(cache.peek_mru(), Some((&3, &3)));
+
+        clock.advance(Duration::from_secs(1));
+        assert_eq!(cache.peek_lru(), None);
+        assert_eq!(cache.peek_mru(), None);
+    }
+
+    #[test]
+    fn test_leaked_drain() {
+        let mut cache = LruCache::new(2);
+        cache.insert(1, 1);
+        cache.insert(2, 2);
+        mem::forget(cache.drain());
+
+        for k in 3..=5 {
+            cache.insert(k, k);
+        }
+
+        assert_eq!(cache.len(), 2);
+        assert_eq!(order(&cache), [4, 5]);
+    }
+
+    #[test]
+    fn test_ttl() {
+        let clock = ManualClock::new();
+        let mut cache = LruCache::new(3);
+        cache.set_clock(clock.clone());
+
+        cache.insert_with_ttl(1, "a", Duration::from_secs(10));
+        cache.insert(2, "b");
+        clock.advance(Duration::from_secs(9));
+
+        assert_eq!(cache.get(&1), Some(&"a"));
+        assert_eq!(cache.peek(&1), Some(&"a"));
+
+        clock.advance(Duration::from_secs(1));
+        assert_eq!(cache.peek(&1), None);
+        assert!(!cache.contains_key(&1));
+        assert_eq!(cache.len(), 2);
+
+        assert_eq!(cache.get(&1), None);
+        assert_eq!(cache.len(), 1);
+        assert_eq!(cache.get(&2), Some(&"b"));
+
+        cache.insert_with_ttl(3, "c", Duration::from_secs(5));
+        clock.advance(Duration::from_secs(5));
+        assert_eq!(cache.insert(3, "d"), None);
+        assert_eq!(cache.get(&3), Some(&"d"));
+    }
+
+    #[test]
+    fn test_default_ttl_and_purge() {
+        let clock = ManualClock::new();
+        let mut cache = LruCache::new(4);
+        cache.set_clock(clock.clone());
+        cache.set_default_ttl(Some(Duration::from_secs(60)));
+        let events = record_evictions(&mut cache);
+
+        cache.insert(1, 10);
+        cache.insert_with_ttl(2, 20, Duration::from_secs(120));
+        clock.advance(Duration::from_secs(30));
+        cache.insert(3, 30);
+        clock.advance(Duration::from_secs(30));
+
+        assert_eq!(cache.purge_expired(), 1);
+        assert_eq!(order(&cache), [2, 3]);
+
+        clock.advance(Duration::from_secs(30));
+        cache.entry(3).and_modify(|v| *v += 1).or_insert(0).unwrap();
+        assert_eq!(cache.peek(&3), Some(&0));
+
+        clock.advance(Duration::from_secs(30));
+        assert_eq!(cache.purge_expired(), 1);
+        assert_eq!(order(&cache), [3]);
+
+        assert_eq!(
+            *events.lock().unwrap(),
+            [
+                (1, 10, EvictionReason::Expired),
+                (3, 30, EvictionReason::Expired),
+                (2, 20, EvictionReason::Expired),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_weigher() {
+        let mut cache = LruCache::with_weigher(10, |_k: &u32, v: &String| v.len());
+
+        cache.insert(1, "aaaa".to_owned());
+        cache.insert(2, "bbb".to_owned());
+        cache.insert(3, "cc".to_owned());
+        assert_eq!(cache.total_weight(), 9);
+
+        assert_eq!(
+            cache.push(4, "dddd".to_owned()),
+            Some((1, "aaaa".to_owned()))
+        );
+        assert_eq!(cache.total_weight(), 9);
+        assert_eq!(order(&cache), [2, 3, 4]);
+
+        cache.get(&2);
+        assert_eq!(
+            cache.insert(4, "dddddd".to_owned()),
+            Some("dddd".to_owned())
+        );
+        assert_eq!(order(&cache), [2, 4]);
+        assert_eq!(cache.total_weight(), 9);
+
+        cache.remove(&2);
+        assert_eq!(cache.total_weight(), 6);
+
+        assert_eq!(cache.resize(5), [(4, "dddddd".to_owned())]);
+        assert_eq!(cache.total_weight(), 0);
+    }
+
+    #[test]
+    fn test_weigher_rejects_oversized() {
+        let mut cache = LruCache::with_weigher(4, |_k: &u32, v: &&str| v.len());
+
+        cache.insert(1, "a");
+        cache.insert(2, "bb");
+
+        assert_eq!(cache.push(3, "ccccc"), Some((3, "ccccc")));
+        assert!(!cache.insert_if_absent(3, "ccccc"));
+        assert_eq!(order(&cache), [1, 2]);
+
+        assert_eq!(cache.insert(2, "ddddd"), Some("bb"));
+        assert_eq!(order(&cache), [1]);
+        assert_eq!(cache.total_weight(), 1);
+
+        cache.insert(2, "bb");
+        assert_eq!(cache.push(2, "ddddd"), Some((2, "bb")));
+        cache.insert(2, "bb");
+        assert_eq!(
+            cache.insert_or_reject(2, "ddddd"),
+            Err((Some("bb"), (2, "ddddd")))
+        );
+        assert_eq!(order(&cache), [1]);
+
+        assert_eq!(cache.entry(3).or_insert("ccccc"), Err((3, "ccccc")));
+
+        match cache.entry(1) {
+            Entry::Occupied(entry) => assert_eq!(entry.insert("eeeee"), Err((1, "eeeee"))),
+            Entry::Vacant(_) => unreachable!(),
+        }
+
+        assert!(cache.is_empty());
+        assert_eq!(cache.total_weight(), 0);
+    }
+
+    #[test]
+    fn test_weigher_near_usize_max() {
+        let mut cache = LruCache::with_weigher(usize::MAX, |_k: &u32, v: &usize| *v);
+
+        cache.insert(1, 1);
+        cache.insert(2, usize::MAX);
+        assert_eq!(order(&cache), [2]);
+        assert_eq!(cache.total_weight(), usize::MAX);
+
+        cache.insert(1, usize::MAX - 1);
+        cache.insert(2, 1);
+        assert_eq!(order(&cache), [1, 2]);
+        assert_eq!(cache.insert(2, usize::MAX), Some(1));
+        assert_eq!(order(&cache), [2]);
+        assert_eq!(cache.total_weight(), usize::MAX);
+    }
+
+    #[test]
+    fn test_pin() {
+        let mut cache = LruCache::new(3);
+        let evicted = record_evictions(&mut cache);
+
+        for k in 1..=3 {
+            cache.insert(k, k);
+        }
+
+        assert!(cache.pin(&1));
+        assert!(cache.pin(&1));
+        assert!(!cache.pin(&4));
+        assert_eq!(cache.pinned_len(), 1);
+        assert_eq!(order(&cache), [1, 2, 3]);
+
+        cache.insert(4, 4);
+        assert_eq!(order(&cache), [1, 3, 4]);
+
+        cache.pin(&3);
+        cache.pin(&4);
+        assert_eq!(cache.pinned_len(), 3);
+        assert_eq!(cache.insert(5, 5), None);
+        assert_eq!(cache.push(6, 6), Some((6, 6)));
+        assert_eq!(cache.insert_or_reject(8, 8), Err((None, (8, 8))));
+        assert_eq!(cache.entry(9).or_insert(9), Err((9, 9)));
+        assert_eq!(cache.insert_or_reject(1, 10), Ok(Some(1)));
+        assert_eq!(order(&cache), [3, 4, 1]);
+        assert_eq!(cache.resize(2), []);
+        assert_eq!(cache.len(), 3);
+
+        assert!(cache.unpin(&4));
+        assert_eq!(cache.pinned_len(), 2);
+        cache.resize(3);
+        cache.insert(7, 7);
+        assert_eq!(order(&cache), [3, 1, 7]);
+
+        cache.remove(&3);
+        assert_eq!(cache.pinned_len(), 1);
+        assert_eq!(
+            *evicted.lock().unwrap(),
+            [
+                (2, 2, EvictionReason::Capacity),
+                (5, 5, EvictionReason::Capacity),
+                (6, 6, EvictionReason::Capacity),
+                (8, 8, EvictionReason::Capacity),
+                (9, 9, EvictionReason::Capacity),
+                (1, 1, EvictionReason::Replaced),
+                (4, 4, EvictionReason::Capacity),
+                (3, 3, EvictionReason::Removed),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_pin_with_heavy_weights() {
+        let mut cache = LruCache::with_weigher(usize::MAX, |_k: &u32, v: &usize| *v);
+
+        cache.insert(1, 1);
+        cache.pin(&1);
+        assert_eq!(
+            cache.insert_or_reject(2, usize::MAX),
+            Err((None, (2, usize::MAX)))
+        );
+        assert_eq!(cache.insert_or_reject(2, usize::MAX - 1), Ok(None));
+        cache.pin(&2);
+        assert_eq!(
+            cache.insert_or_reject(1, usize::MAX),
+            Err((Some(1), (1, usize::MAX)))
+        );
+        assert_eq!(cache.pinned_len(), 1);
+        assert_eq!(order(&cache), [2]);
+        assert_eq!(
+            cache.insert_or_reject(2, usize::MAX),
+            Ok(Some(usize::MAX - 1))
+        );
+        assert_eq!(cache.total_weight(), usize::MAX);
+    }
+
+    #[test]
+    fn test_policy_vacant_victim_falls_back_to_lru() {
+        struct Stale;
+
+        impl EvictionPolicy for Stale {
+            fn victim(&mut self) -> Option<usize> {
+                Some(999)
+            }
+        }
+
+        let mut cache = LruCache::with_policy(2, Stale);
+
+        for k in 1..=3 {
+            cache.insert(k, k);
+        }
+
+        assert_eq!(order(&cache), [2, 3]);
+    }
+
+    #[test]
+    fn test_pin_skips_policy_victim() {
+        let mut cache = LruCache::with_policy(3, policy::Lfu::new());
+
+        for k in 1..=3 {
+            cache.insert(k, k);
+        }
+
+        cache.get(&2);
+        cache.get(&3);
+        cache.pin(&1);
+        cache.insert(4, 4);
+        assert!(cache.contains_key(&1));
+        assert!(!cache.contains_key(&2));
+    }
+
+    #[test]
+    fn test_expired_pins_make_room() {
+        let clock = ManualClock::new();
+        let mut cache = LruCache::new(2);
+        cache.set_clock(clock.clone());
+        cache.insert_with_ttl(1, 1, Duration::from_secs(1));
+        cache.insert(2, 2);
+        cache.pin(&1);
+        cache.pin(&2);
+        clock.advance(Duration::from_secs(1));
+
+        cache.insert(3, 3);
+        assert_eq!(order(&cache), [2, 3]);
+        assert_eq!(cache.pinned_len(), 1);
+    }
+
+    #[test]
+    fn test_replace_accesses_once() {
+        use std::{cell::Cell, rc::Rc};
+
+        struct Counting(Rc<Cell<usize>>);
+
+        impl EvictionPolicy for Counting {
+            fn on_access(&mut self, _slot: usize) {
+                self.0.set(self.0.get() + 1);
+            }
+
+            fn victim(&mut self) -> Option<usize> {
+                None
+            }
+        }
+
+        let accesses = Rc::new(Cell::new(0));
+        let mut cache = LruCache::with_policy(2, Counting(accesses.clone()));
+        cache.insert(1, 1);
+
+        match cache.entry(1) {
+            Entry::Occupied(entry) => assert_eq!(entry.insert(2), Ok(1)),
+            Entry::Vacant(_) => unreachable!(),
+        }
+
+        assert_eq!(accesses.get(), 1);
+        cache.insert(1, 3);
+        assert_eq!(accesses.get(), 2);
+        cache.push(1, 4);
+        assert_eq!(accesses.get(), 3);
+    }
+
+    #[test]
+    fn test_fifo_policy() {
+        let mut cache = LruCache::with_policy(3, policy::Fifo::new());
+
+        for k in 1..=3 {
+            cache.insert(k, k);
+        }
+
+        cache.get(&1);
+        cache.insert(4, 4);
+        assert!(!cache.contains_key(&1));
+
+        cache.remove(&3);
+        cache.insert(5, 5);
+        cache.insert(6, 6);
+        assert_eq!(cache.keys().copied().collect::<Vec<_>>(), [4, 5, 6]);
+    }
+
+    #[test]
+    fn test_lfu_policy() {
+        let mut cache = LruCache::with_policy(3, policy::Lfu::new());
+
+        for k in 1..=3 {
+            cache.insert(k, k);
+        }
+
+        cache.get(&1);
+        cache.get(&1);
+        cache.get(&3);
+        cache.insert(4, 4);
+        assert!(!cache.contains_key(&2));
+
+        cache.insert(5, 5);
+        assert!(!cache.contains_key(&4));
+
+        cache.insert(3, 30);
+        cache.insert(6, 6);
+        assert!(!cache.contains_key(&5));
+        assert_eq!(order(&cache), [1, 3, 6]);
+    }
+
+    #[test]
+    fn test_second_chance_policy() {
+        let mut cache = LruCache::with_policy(3, policy::SecondChance::new());
+
+        for k in 1..=3 {
+            cache.insert(k, k);
+        }
+
+        cache.get(&1);
+        cache.get(&2);
+        cache.insert(4, 4);
+        assert!(!cache.contains_key(&3));
+
+        cache.insert(5, 5);
+        assert!(!cache.contains_key(&1));
+
+        cache.get(&4);
+        cache.insert(6, 6);
+        assert!(!cache.contains_key(&2));
+        assert_eq!(cache.len(), 3);
+    }
+
+    #[test]
+    fn test_policy_spares_replaced_entry() {
+        let mut cache =
+            LruCache::with_weigher_and_policy(4, |_k: &u32, v: &&str| v.len(), policy::Lfu::new());
+
+        cache.insert(1, "a");
+        cache.insert(2, "b");
+        cache.get(&2);
+        cache.get(&2);
+        cache.insert(1, "aaaa");
+
+        assert_eq!(order(&cache), [1]);
+        assert_eq!(cache.total_weight(), 4);
+    }
+
+    #[test]
+    fn test_slru_policy_resists_scans() {
+        let mut lru = LruCache::new(10);
+        let mut slru = LruCache::with_policy(10, policy::Slru::with_ratio(10, 0.8));
+
+        for k in 0..5 {
+            lru.insert(k, k);
+            slru.insert(k, k);
+            lru.get(&k);
+            slru.get(&k);
+        }
+
+        for k in 100..1_000 {
+            lru.insert(k, k);
+            slru.insert(k, k);
+        }
+
+        assert!((0..5).all(|k| slru.contains_key(&k)));
+        assert!((0..5).all(|k| !lru.contains_key(&k)));
+        assert_eq!(slru.len(), 10);
+    }
+
+    #[test]
+    fn test_slru_policy_demotes() {
+        let mut cache = LruCache::with_policy(4, policy::Slru::new(2));
+
+        for k in 1..=4 {
+            cache.insert(k, k);
+        }
+
+        cache.get(&1);
+        cache.get(&2);
+        cache.get(&3);
+        cache.insert(5, 5);
+        assert!(!cache.contains_key(&4));
+
+        cache.insert(6, 6);
+        assert!(!cache.contains_key(&1));
+        assert!(cache.contains_key(&2) && cache.contains_key(&3));
+    }
+
+    #[test]
+    fn test_cache_trait() {
+        fn fill(cache: &mut dyn Cache<u32, u32>) {
+            for k in 0..4 {
+                cache.insert(k, k);
+            }
+        }
+
+        let mut lru = LruCache::new(3);
+        let mut arc = ArcCache::new(3);
+        fill(&mut lru);
+        fill(&mut arc);
+
+        for cache in [&mut lru as &mut dyn Cache<u32, u32>, &mut arc] {
+            assert_eq!(cache.len(), 3);
+            assert_eq!(cache.capacity(), 3);
+            assert_eq!(cache.get(&0), None);
+            assert_eq!(cache.get(&3), Some(&3));
+            assert_eq!(cache.remove(&3), Some(3));
+        }
+    }
+
+    #[test]
+    fn test_tiny_lfu_policy_admission() {
+        let mut cache = LruCache::with_policy(100, policy::TinyLfu::new(100));
+
+        for k in 0..100 {
+            cache.insert(k, k);
+        }
+
+        for _ in 0..5 {
+            for k in 0..50 {
+                cache.get(&k);
+            }
+        }
+
+        for k in 1_000..2_000 {
+            cache.get(&k);
+            cache.insert(k, k);
+        }
+
+        assert!((0..50).all(|k| cache.contains_key(&k)));
+        assert_eq!(cache.len(), 100);
+    }
+
+    #[test]
+    fn test_tiny_lfu_rejects_rare_candidate() {
+        let mut cache = LruCache::with_policy(10, policy::TinyLfu::new(10));
+
+        // Misses count towards the frequency of key 0, which is then inserted
+        // first and spilled to the main region untouched.
+        for _ in 0..5 {
+            cache.get(&0);
+        }
+
+        for k in 0..10 {
+            cache.insert(k, k);
+        }
+
+        assert_eq!(order(&cache), (0..10).collect::<Vec<_>>());
+
+        // Key 9 leaves the window and loses against key 0, the main region's
+        // victim, so it is evicted in its place.
+        cache.insert(100, 100);
+        assert!(cache.contains_key(&0));
+        assert!(!cache.contains_key(&9));
+        assert!(cache.contains_key(&100));
+    }
+
+    #[test]
+    fn test_stats() {
+        let clock = ManualClock::new();
+        let mut cache = LruCache::with_weigher(4, |_k: &u32, v: &usize| *v).with_stats();
+        cache.set_clock(clock.clone());
+
+        assert_eq!(cache.stats(), CacheStats::default());
+
+        cache.insert(1, 1);
+        cache.insert(2, 1);
+        cache.insert(1, 2);
+        assert_eq!(cache.get(&1), Some(&2));
+        assert_eq!(cache.get(&3), None);
+        cache.entry(3).or_insert(1).unwrap();
+        cache.insert(4, 1);
+        cache.insert(5, 9);
+        cache.remove(&3);
+        cache.insert_with_ttl(6, 1, Duration::from_secs(1));
+        clock.advance(Duration::from_secs(1));
+        assert_eq!(cache.get(&6), None);
+
+        let stats = cache.stats();
+        assert_eq!(
+            stats,
+            CacheStats {
+                hits: 1,
+                misses: 3,
+                inserts: 5,
+                updates: 1,
+                evictions: 2,
+                expirations: 1,
+                removals: 1,
+                len: 2,
+                weight: 3,
+            }
+        );
+        assert_eq!(stats.hit_rate(), 0.25);
+
+        cache.reset_stats();
+        assert_eq!(
+            cache.stats(),
+            CacheStats {
+                len: 2,
+                weight: 3,
+                ..CacheStats::default()
+            }
+        );
+    }
+
+    #[test]
+    fn test_get_or_insert_with() {
+        let mut cache = LruCache::new(2).with_stats();
+
+        assert_eq!(cache.get_or_insert_with(1, || 10), Ok(&10));
+        assert_eq!(cache.get_or_insert_with(2, || 20), Ok(&20));
+        assert_eq!(cache.get_or_insert_with(1, || unreachable!()), Ok(&10));
+        assert_eq!(cache.get_or_insert_with(3, || 30), Ok(&30));
+        assert_eq!(order(&cache), [1, 3]);
+
+        assert_eq!(
+            cache.try_get_or_insert_with(4, || Err("failed")),
+            Err("failed")
+        );
+        assert_eq!(order(&cache), [1, 3]);
+        assert_eq!(
+            cache.try_get_or_insert_with(1, || Err("failed")),
+            Ok(Ok(&10))
+        );
+        assert_eq!(order(&cache), [3, 1]);
+
+        let stats = cache.stats();
+        assert_eq!((stats.hits, stats.misses, stats.inserts), (2, 4, 3));
+    }
+
+    #[test]
+    fn test_hasher() {
+        use std::{
+            collections::hash_map::{DefaultHasher, RandomState},
+            hash::BuildHasherDefault,
+        };
+
+        type Fixed = BuildHasherDefault<DefaultHasher>;
+
+        // The default hasher is the same with and without `std`.
+        let _: LruCache<u32, u32, Lru, NoStats, hashbrown::DefaultHashBuilder> = LruCache::new(1);
+
+        let mut cache = LruCache::with_capacity_and_hasher(1, RandomState::new());
+        cache.insert(1, 1);
+        cache.insert(2, 2);
+        assert_eq!(order(&cache), [2]);
+
+        let mut cache = LruCache::with_capacity_and_hasher(2, Fixed::default());
+        cache.insert("a", 1);
+        cache.insert("b", 2);
+        *cache.entry("a").or_insert(0).unwrap() += 10;
+        cache.insert("c", 3);
+        assert_eq!(order(&cache), ["a", "c"]);
+        assert_eq!(cache.get("a"), Some(&11));
+
+        let mut cache = LruCache::with_hasher(Fixed::default());
+        for k in 0..1_000 {
+            cache.insert(k, k);
+        }
+
+        assert_eq!(cache.len(), 1_000);
+        assert_eq!(cache.capacity(), usize::MAX);
+
+        let mut cache =
+            LruCache::with_weigher_and_hasher(4, |_k: &u32, v: &&str| v.len(), Fixed::default());
+        cache.insert(1, "aa");
+        cache.insert(2, "bb");
+        cache.insert(3, "c");
+        assert_eq!(order(&cache), [2, 3]);
+
+        let mut cache = LruCache::with_policy_and_hasher(3, policy::Slru::new(2), Fixed::default());
+        for k in 1..=4 {
+            cache.insert(k, k);
+        }
+        assert_eq!(cache.len(), 3);
+
+        let mut cache = LruCache::with_weigher_policy_and_hasher(
+            3,
+            |_k: &u32, v: &&str| v.len(),
+            policy::TinyLfu::new(3),
+            Fixed::default(),
+        );
+        cache.insert(1, "a");
+        cache.insert(2, "bb");
+        cache.insert(3, "c");
+        assert!(cache.total_weight() <= 3);
+    }
 }
@@ -0,0 +1,153 @@
+use core::{
+    convert::Infallible,
+    fmt,
+    hash::{BuildHasher, Hash},
+};
+
+use crate::{policy::Lru, DefaultHashBuilder, EvictionPolicy, LruCache, NoStats, StatsRecorder};
+
+/// An [`LruCache`] that computes missing values itself with a loader
+/// function.
+///
+/// A loader returning `V` is used through [`get`](LoadingCache::get), one
+/// returning `Result<V, E>` through [`try_get`](LoadingCache::try_get).
+pub struct LoadingCache<K, V, F, P = Lru, R = NoStats, S = DefaultHashBuilder> {
+    cache: LruCache<K, V, P, R, S>,
+    loader: F,
+}
+
+impl<K, V, F, P, R, S> LoadingCache<K, V, F, P, R, S>
+where
+    K: Eq + Hash,
+    P: EvictionPolicy,
+    R: StatsRecorder,
+    S: BuildHasher,
+{
+    /// Wraps `cache`, keeping its capacity, policy and other settings.
+    pub fn new(cache: LruCache<K, V, P, R, S>, loader: F) -> Self {
+        Self { cache, loader }
+    }
+
+    pub fn cache(&self) -> &LruCache<K, V, P, R, S> {
+        &self.cache
+    }
+
+    /// Gives direct access to the cache, for example to insert or remove
+    /// entries without going through the loader.
+    pub fn cache_mut(&mut self) -> &mut LruCache<K, V, P, R, S> {
+        &mut self.cache
+    }
+
+    pub fn into_inner(self) -> LruCache<K, V, P, R, S> {
+        self.cache
+    }
+
+    /// Returns the value for a key, loading and inserting it if it is absent.
+    ///
+    /// A loaded value that cannot be stored, as in a zero-capacity cache, is
+    /// handed back with its key instead. See
+    /// [`LruCache::get_or_insert_with`].
+    pub fn get(&mut self, k: K) -> Result<&V, (K, V)>
+    where
+        F: FnMut(&K) -> V,
+    {
+        let loader = &mut self.loader;
+
+        match self
+            .cache
+            .try_get_or_insert_with_key(k, |k| Ok::<_, Infallible>(loader(k)))
+        {
+            Ok(loaded) => loaded,
+            Err(never) => match never {},
+        }
+    }
+
+    /// Returns the value for a key, loading and inserting it if it is absent.
+    /// A failed load inserts nothing and returns the loader's error, while a
+    /// loaded value that cannot be stored is handed back like with
+    /// [`get`](Self::get).
+    pub fn try_get<E>(&mut self, k: K) -> Result<Result<&V, (K, V)>, E>
+    where
+        F: FnMut(&K) -> Result<V, E>,
+    {
+        self.cache.try_get_or_insert_with_key(k, &mut self.loader)
+    }
+}
+
+impl<K, V, F, P, R, S> fmt::Debug for LoadingCache<K, V, F, P, R, S>
+where
+    K: fmt::Debug,
+    V: fmt::Debug,
+    P: fmt::Debug,
+    R: fmt::Debug,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("LoadingCache")
+            .field("cache", &self.cache)
+            .finish_non_exhaustive()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{format, string::ToString};
+
+    use super::*;
+
+    #[test]
+    fn test_loads_once() {
+        let mut loads = 0;
+        let mut cache = LoadingCache::new(LruCache::new(2), |k: &u32| {
+            loads += 1;
+            k * 10
+        });
+
+        assert_eq!(cache.get(1), Ok(&10));
+        assert_eq!(cache.get(1), Ok(&10));
+        assert_eq!(cache.get(2), Ok(&20));
+        assert_eq!(cache.get(3), Ok(&30));
+        assert!(!cache.cache().contains_key(&1));
+        assert_eq!(cache.get(1), Ok(&10));
+
+        drop(cache);
+        assert_eq!(loads, 4);
+    }
+
+    #[test]
+    fn test_failed_load_inserts_nothing() {
+        let mut cache = LoadingCache::new(LruCache::new(2), |k: &u32| {
+            if k % 2 == 0 {
+                Ok(k / 2)
+            } else {
+                Err(format!("{k} is odd"))
+            }
+        });
+
+        assert_eq!(cache.try_get(4), Ok(Ok(&2)));
+        assert_eq!(cache.try_get(3), Err("3 is odd".to_string()));
+        assert_eq!(cache.cache().len(), 1);
+
+        cache.cache_mut().insert(3, 1);
+        assert_eq!(cache.try_get(3), Ok(Ok(&1)));
+    }
+
+    #[test]
+    fn test_zero_capacity_hands_back_loaded_values() {
+        let mut cache = LoadingCache::new(LruCache::new(0), |k: &u32| k * 10);
+        assert_eq!(cache.get(1), Err((1, 10)));
+        assert!(cache.cache().is_empty());
+
+        let mut cache = LoadingCache::new(LruCache::new(0), |k: &u32| Ok::<_, ()>(k * 10));
+        assert_eq!(cache.try_get(2), Ok(Err((2, 20))));
+    }
+
+    #[test]
+    fn test_pinned_cache_hands_back_loaded_values() {
+        let mut cache = LoadingCache::new(LruCache::new(1), |k: &u32| k * 10);
+        assert_eq!(cache.get(1), Ok(&10));
+        cache.cache_mut().pin(&1);
+
+        assert_eq!(cache.get(2), Err((2, 20)));
+        assert_eq!(cache.get(1), Ok(&10));
+    }
+}
@@ -0,0 +1,550 @@
+//! Eviction policies an [`LruCache`](crate::LruCache) can defer to when it
+//! needs to make room.
+//!
+//! A policy refers to entries by their slot, a small integer that stays fixed
+//! while the entry is cached and may be reused once it has left. Keys are
+//! identified by their hash as computed by the cache, which lets a policy
+//! keep statistics about keys that are not cached.
+
+use alloc::{collections::BTreeSet, vec, vec::Vec};
+
+use crate::{Graph, NodeId};
+
+/// Decides which entry to evict.
+///
+/// The cache reports every insert, hit and removal to the policy, and asks it
+/// for a victim whenever it needs room. The cache itself always keeps entries
+/// in recency order, so a policy that returns `None` from
+/// [`victim`](Self::victim) gets least recently used eviction.
+pub trait EvictionPolicy {
+    /// A new entry whose key hashes to `hash` was stored in `slot`.
+    fn on_insert(&mut self, slot: usize, hash: u64) {
+        let _ = (slot, hash);
+    }
+
+    /// The entry in `slot` was looked up or had its value replaced.
+    fn on_access(&mut self, slot: usize) {
+        let _ = slot;
+    }
+
+    /// A lookup found no entry for a key hashing to `hash`.
+    fn on_miss(&mut self, hash: u64) {
+        let _ = hash;
+    }
+
+    /// The entry in `slot` left the cache, for whatever reason.
+    fn on_remove(&mut self, slot: usize) {
+        let _ = slot;
+    }
+
+    /// Picks the slot to evict, or `None` for the least recently used entry.
+    ///
+    /// The cache falls back to the least recently used entry as well if the
+    /// slot is not occupied, or holds a pinned entry.
+    fn victim(&mut self) -> Option<usize>;
+}
+
+/// Evicts the least recently used entry, the cache's default.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Lru;
+
+impl EvictionPolicy for Lru {
+    fn victim(&mut self) -> Option<usize> {
+        None
+    }
+}
+
+/// Evicts the oldest entry, regardless of how often or recently it was used.
+#[derive(Debug, Default)]
+pub struct Fifo {
+    queue: Graph<usize>,
+    node_ids: Vec<Option<NodeId>>,
+}
+
+impl Fifo {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl EvictionPolicy for Fifo {
+    fn on_insert(&mut self, slot: usize, _hash: u64) {
+        if self.node_ids.len() <= slot {
+            self.node_ids.resize(slot + 1, None);
+        }
+
+        self.node_ids[slot] = Some(self.queue.push_back(slot));
+    }
+
+    fn on_remove(&mut self, slot: usize) {
+        if let Some(node_id) = self.node_ids.get_mut(slot).and_then(Option::take) {
+            self.queue.remove(node_id);
+        }
+    }
+
+    fn victim(&mut self) -> Option<usize> {
+        self.queue.front().copied()
+    }
+}
+
+/// Evicts the least frequently used entry, breaking ties by evicting the one
+/// that was touched longest ago.
+#[derive(Debug, Default)]
+pub struct Lfu {
+    ranking: BTreeSet<(u64, u64, usize)>,
+    ranks: Vec<Option<(u64, u64)>>,
+    tick: u64,
+}
+
+impl Lfu {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn rank(&mut self, slot: usize, frequency: u64) {
+        self.tick += 1;
+        self.ranks[slot] = Some((frequency, self.tick));
+        self.ranking.insert((frequency, self.tick, slot));
+    }
+}
+
+impl EvictionPolicy for Lfu {
+    fn on_insert(&mut self, slot: usize, _hash: u64) {
+        if self.ranks.len() <= slot {
+            self.ranks.resize(slot + 1, None);
+        }
+
+        self.rank(slot, 1);
+    }
+
+    fn on_access(&mut self, slot: usize) {
+        if let Some((frequency, tick)) = self.ranks.get_mut(slot).and_then(Option::take) {
+            self.ranking.remove(&(frequency, tick, slot));
+            self.rank(slot, frequency.saturating_add(1));
+        }
+    }
+
+    fn on_remove(&mut self, slot: usize) {
+        if let Some((frequency, tick)) = self.ranks.get_mut(slot).and_then(Option::take) {
+            self.ranking.remove(&(frequency, tick, slot));
+        }
+    }
+
+    fn victim(&mut self) -> Option<usize> {
+        let &(_frequency, _tick, slot) = self.ranking.first()?;
+        Some(slot)
+    }
+}
+
+/// The CLOCK (second chance) algorithm.
+///
+/// A hand sweeps over the slots, clearing the referenced bit of entries that
+/// were used since it last passed and evicting the first one that was not.
+#[derive(Debug, Default)]
+pub struct SecondChance {
+    referenced: Vec<Option<bool>>,
+    hand: usize,
+    len: usize,
+}
+
+impl SecondChance {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl EvictionPolicy for SecondChance {
+    fn on_insert(&mut self, slot: usize, _hash: u64) {
+        if self.referenced.len() <= slot {
+            self.referenced.resize(slot + 1, None);
+        }
+
+        self.referenced[slot] = Some(false);
+        self.len += 1;
+    }
+
+    fn on_access(&mut self, slot: usize) {
+        if let Some(Some(referenced)) = self.referenced.get_mut(slot) {
+            *referenced = true;
+        }
+    }
+
+    fn on_remove(&mut self, slot: usize) {
+        if let Some(referenced) = self.referenced.get_mut(slot) {
+            if referenced.take().is_some() {
+                self.len -= 1;
+            }
+        }
+    }
+
+    fn victim(&mut self) -> Option<usize> {
+        if self.len == 0 {
+            return None;
+        }
+
+        loop {
+            let slot = self.hand;
+            self.hand = (self.hand + 1) % self.referenced.len();
+
+            match &mut self.referenced[slot] {
+                Some(referenced) if *referenced => *referenced = false,
+                Some(_) => return Some(slot),
+                None => {}
+            }
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Segment {
+    Probation,
+    Protected,
+}
+
+/// Segmented LRU, which keeps one-off entries from flushing out hot ones.
+///
+/// New entries start in a probationary segment and move to a protected one
+/// the first time they are hit. Victims are taken from the least recently used
+/// end of the probationary segment, so a scan over many keys that are never
+/// looked at twice only churns probation. When the protected segment
+/// outgrows its share its least recently used entry is demoted back to
+/// probation.
+#[derive(Debug)]
+pub struct Slru {
+    probation: Graph<usize>,
+    protected: Graph<usize>,
+    segments: Vec<Option<(Segment, NodeId)>>,
+    protected_len: usize,
+    protected_capacity: usize,
+}
+
+impl Slru {
+    /// Creates a policy whose protected segment holds at most
+    /// `protected_capacity` entries.
+    pub fn new(protected_capacity: usize) -> Self {
+        Self {
+            probation: Graph::default(),
+            protected: Graph::default(),
+            segments: Vec::new(),
+            protected_len: 0,
+            protected_capacity,
+        }
+    }
+
+    /// Creates a policy for a cache of `capacity` entries that reserves the
+    /// fraction `protected_ratio` of them, between 0 and 1, for the protected
+    /// segment. A ratio of 0.8 is a common choice.
+    pub fn with_ratio(capacity: usize, protected_ratio: f64) -> Self {
+        let protected_ratio = protected_ratio.clamp(0.0, 1.0);
+        Self::new((capacity as f64 * protected_ratio) as usize)
+    }
+
+    fn push(&mut self, slot: usize, segment: Segment) {
+        let node_id = match segment {
+            Segment::Probation => self.probation.push_back(slot),
+            Segment::Protected => {
+                self.protected_len += 1;
+                self.protected.push_back(slot)
+            }
+        };
+
+        self.segments[slot] = Some((segment, node_id));
+    }
+
+    fn unlink(&mut self, slot: usize) -> Option<Segment> {
+        let (segment, node_id) = self.segments.get_mut(slot)?.take()?;
+
+        match segment {
+            Segment::Probation => self.probation.remove(node_id),
+            Segment::Protected => {
+                self.protected_len -= 1;
+                self.protected.remove(node_id)
+            }
+        };
+
+        Some(segment)
+    }
+}
+
+impl EvictionPolicy for Slru {
+    fn on_insert(&mut self, slot: usize, _hash: u64) {
+        if self.segments.len() <= slot {
+            self.segments.resize(slot + 1, None);
+        }
+
+        self.push(slot, Segment::Probation);
+    }
+
+    fn on_access(&mut self, slot: usize) {
+        if self.unlink(slot).is_none() {
+            return;
+        }
+
+        if self.protected_capacity == 0 {
+            self.push(slot, Segment::Probation);
+            return;
+        }
+
+        if self.protected_len == self.protected_capacity {
+            let demoted = self.protected.pop_front().unwrap();
+            self.protected_len -= 1;
+            self.push(demoted, Segment::Probation);
+        }
+
+        self.push(slot, Segment::Protected);
+    }
+
+    fn on_remove(&mut self, slot: usize) {
+        self.unlink(slot);
+    }
+
+    fn victim(&mut self) -> Option<usize> {
+        self.probation
+            .front()
+            .or_else(|| self.protected.front())
+            .copied()
+    }
+}
+
+/// W-TinyLFU: an admission window in front of a segmented LRU, guarded by a
+/// frequency filter.
+///
+/// New entries land in a small LRU window. When the window overflows while
+/// the cache is full, its least recently used entry becomes a candidate for
+/// the main segmented LRU ([`Slru`]) and competes with the main region's
+/// victim. Whichever key has been requested less often, according to a
+/// count-min sketch that also counts misses, is evicted. One-hit wonders
+/// therefore rarely push out entries that are used over and over.
+///
+/// The sketch halves its counters periodically so that frequencies age.
+#[derive(Debug)]
+pub struct TinyLfu {
+    window: Graph<usize>,
+    window_ids: Vec<Option<NodeId>>,
+    window_len: usize,
+    window_capacity: usize,
+    main: Slru,
+    hashes: Vec<u64>,
+    sketch: CountMinSketch,
+    /// The hash of the last miss, already counted if its key is inserted next.
+    last_miss: Option<u64>,
+}
+
+impl TinyLfu {
+    /// Creates a policy for a cache of `capacity` entries, giving 1% of it to
+    /// the window and 80% of the rest to the protected segment.
+    pub fn new(capacity: usize) -> Self {
+        let window_capacity = (capacity / 100).max(1);
+        let main_capacity = capacity.saturating_sub(window_capacity);
+
+        Self {
+            window: Graph::default(),
+            window_ids: Vec::new(),
+            window_len: 0,
+            window_capacity,
+            main: Slru::new(main_capacity * 4 / 5),
+            hashes: Vec::new(),
+            sketch: CountMinSketch::new(capacity),
+            last_miss: None,
+        }
+    }
+
+    /// Moves the least recently used window entry to the main region.
+    fn spill_window(&mut self) -> Option<usize> {
+        let slot = self.window.pop_front()?;
+        self.window_ids[slot] = None;
+        self.window_len -= 1;
+        self.main.on_insert(slot, self.hashes[slot]);
+        Some(slot)
+    }
+}
+
+impl EvictionPolicy for TinyLfu {
+    fn on_insert(&mut self, slot: usize, hash: u64) {
+        if self.hashes.len() <= slot {
+            self.hashes.resize(slot + 1, 0);
+            self.window_ids.resize(slot + 1, None);
+        }
+
+        // A lookup that missed followed by the insert is a single request.
+        if self.last_miss.take() != Some(hash) {
+            self.sketch.increment(hash);
+        }
+
+        self.hashes[slot] = hash;
+        self.window_ids[slot] = Some(self.window.push_back(slot));
+        self.window_len += 1;
+
+        while self.window_len > self.window_capacity {
+            self.spill_window();
+        }
+    }
+
+    fn on_access(&mut self, slot: usize) {
+        self.last_miss = None;
+
+        let Some(&hash) = self.hashes.get(slot) else {
+            return;
+        };
+
+        self.sketch.increment(hash);
+
+        match self.window_ids[slot] {
+            Some(node_id) => {
+                self.window.move_to_back(node_id);
+            }
+            None => self.main.on_access(slot),
+        }
+    }
+
+    fn on_miss(&mut self, hash: u64) {
+        self.sketch.increment(hash);
+        self.last_miss = Some(hash);
+    }
+
+    fn on_remove(&mut self, slot: usize) {
+        match self.window_ids.get_mut(slot).and_then(Option::take) {
+            Some(node_id) => {
+                self.window.remove(node_id);
+                self.window_len -= 1;
+            }
+            None => self.main.on_remove(slot),
+        }
+    }
+
+    fn victim(&mut self) -> Option<usize> {
+        // The entry about to be inserted will push the window's least recently
+        // used entry out, so that is the candidate for the main region.
+        let candidate = if self.window_len >= self.window_capacity {
+            self.window.front().copied()
+        } else {
+            None
+        };
+
+        match (candidate, self.main.victim()) {
+            (Some(candidate), Some(victim)) => {
+                let candidate_frequency = self.sketch.estimate(self.hashes[candidate]);
+                let victim_frequency = self.sketch.estimate(self.hashes[victim]);
+
+                if candidate_frequency > victim_frequency {
+                    self.spill_window();
+                    Some(victim)
+                } else {
+                    Some(candidate)
+                }
+            }
+            (candidate, victim) => victim
+                .or(candidate)
+                .or_else(|| self.window.front().copied()),
+        }
+    }
+}
+
+/// A count-min sketch of 4-bit counters estimating how often each key hash
+/// was seen, halving all counters after a sample of increments.
+#[derive(Debug)]
+struct CountMinSketch {
+    counters: Vec<u8>,
+    width: usize,
+    additions: usize,
+    sample_size: usize,
+}
+
+impl CountMinSketch {
+    const DEPTH: usize = 4;
+    const MAX: u8 = 15;
+    const SEEDS: [u64; Self::DEPTH] = [
+        0x9e37_79b9_7f4a_7c15,
+        0xc2b2_ae3d_27d4_eb4f,
+        0x1656_67b1_9e37_79f9,
+        0x27d4_eb2f_1656_67c5,
+    ];
+
+    fn new(capacity: usize) -> Self {
+        let width = (capacity.max(8) * 2).next_power_of_two();
+
+        Self {
+            counters: vec![0; width * Self::DEPTH],
+            width,
+            additions: 0,
+            sample_size: capacity.max(1).saturating_mul(10),
+        }
+    }
+
+    fn index(&self, hash: u64, row: usize) -> usize {
+        let mixed = (hash ^ Self::SEEDS[row]).wrapping_mul(Self::SEEDS[row] | 1);
+        row * self.width + (mixed >> 32) as usize % self.width
+    }
+
+    fn increment(&mut self, hash: u64) {
+        for row in 0..Self::DEPTH {
+            let index = self.index(hash, row);
+            self.counters[index] = (self.counters[index] + 1).min(Self::MAX);
+        }
+
+        self.additions += 1;
+
+        if self.additions >= self.sample_size {
+            self.age();
+        }
+    }
+
+    fn estimate(&self, hash: u64) -> u8 {
+        (0..Self::DEPTH)
+            .map(|row| self.counters[self.index(hash, row)])
+            .min()
+            .unwrap_or(0)
+    }
+
+    fn age(&mut self) {
+        for counter in &mut self.counters {
+            *counter /= 2;
+        }
+
+        self.additions /= 2;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sketch_estimates_and_ages() {
+        let mut sketch = CountMinSketch::new(16);
+
+        for _ in 0..6 {
+            sketch.increment(1);
+        }
+
+        sketch.increment(2);
+        assert_eq!(sketch.estimate(1), 6);
+        assert_eq!(sketch.estimate(2), 1);
+        assert_eq!(sketch.estimate(3), 0);
+
+        for _ in 0..40 {
+            sketch.increment(1);
+        }
+
+        assert!(sketch.estimate(1) <= CountMinSketch::MAX);
+
+        sketch.age();
+        assert!(sketch.estimate(1) < CountMinSketch::MAX);
+        assert_eq!(sketch.estimate(2), 0);
+    }
+
+    #[test]
+    fn test_tiny_lfu_counts_miss_and_insert_once() {
+        let mut policy = TinyLfu::new(16);
+
+        policy.on_miss(1);
+        policy.on_insert(0, 1);
+        assert_eq!(policy.sketch.estimate(1), 1);
+
+        policy.on_miss(2);
+        policy.on_insert(1, 3);
+        policy.on_insert(2, 2);
+        assert_eq!(policy.sketch.estimate(2), 2);
+        assert_eq!(policy.sketch.estimate(3), 1);
+    }
+}
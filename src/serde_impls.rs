@@ -0,0 +1,281 @@
+use alloc::vec::Vec;
+use core::{
+    fmt,
+    hash::{BuildHasher, Hash},
+    iter,
+    marker::PhantomData,
+};
+
+use serde::{
+    de::{self, MapAccess, SeqAccess, Visitor},
+    ser::{SerializeSeq, SerializeStruct},
+    Deserialize, Deserializer, Serialize, Serializer,
+};
+
+use crate::{EvictionPolicy, LruCache, StatsRecorder};
+
+const FIELDS: &[&str] = &["capacity", "entries"];
+
+/// Writes the capacity and the entries from least to most recently used.
+/// Expired entries are left out, and time-to-lives, weights and the policy's
+/// state are not written.
+impl<K, V, P, R, H> Serialize for LruCache<K, V, P, R, H>
+where
+    K: Serialize,
+    V: Serialize,
+{
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        let mut state = serializer.serialize_struct("LruCache", FIELDS.len())?;
+        state.serialize_field("capacity", &self.capacity)?;
+        state.serialize_field("entries", &Entries(self))?;
+        state.end()
+    }
+}
+
+struct Entries<'a, K, V, P, R, H>(&'a LruCache<K, V, P, R, H>);
+
+impl<K, V, P, R, H> Serialize for Entries<'_, K, V, P, R, H>
+where
+    K: Serialize,
+    V: Serialize,
+{
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        let cache = self.0;
+        let now = cache.clock.now();
+        let live = || {
+            let mut node_id = cache.graph.head_id;
+
+            iter::from_fn(move || loop {
+                let node = cache.graph.node(node_id?).unwrap();
+                node_id = node.next_id;
+
+                if !node.element.is_expired(now) {
+                    return Some(&node.element);
+                }
+            })
+        };
+
+        let mut seq = serializer.serialize_seq(Some(live().count()))?;
+
+        for item in live() {
+            seq.serialize_element(&(&item.key, &item.value))?;
+        }
+
+        seq.end()
+    }
+}
+
+/// Reads a cache written by its `Serialize` implementation, inserting the
+/// entries in order so that their recency is restored. The entries never
+/// expire and weigh 1 each, and the policy and stats recorder start afresh.
+impl<'de, K, V, P, R, H> Deserialize<'de> for LruCache<K, V, P, R, H>
+where
+    K: Deserialize<'de> + Eq + Hash,
+    V: Deserialize<'de>,
+    P: EvictionPolicy + Default,
+    R: StatsRecorder + Default,
+    H: BuildHasher + Default,
+{
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        deserializer.deserialize_struct("LruCache", FIELDS, CacheVisitor(PhantomData))
+    }
+}
+
+struct CacheVisitor<K, V, P, R, H>(PhantomData<LruCache<K, V, P, R, H>>);
+
+impl<K, V, P, R, H> CacheVisitor<K, V, P, R, H>
+where
+    K: Eq + Hash,
+    P: EvictionPolicy + Default,
+    R: StatsRecorder + Default,
+    H: BuildHasher + Default,
+{
+    fn build(capacity: usize, entries: Vec<(K, V)>) -> LruCache<K, V, P, R, H> {
+        let preallocate = entries.len().min(capacity);
+        let mut cache =
+            LruCache::from_parts(capacity, preallocate, None, P::default(), H::default());
+
+        for (k, v) in entries {
+            cache.insert(k, v);
+        }
+
+        // Attached only now so that restoring the entries is not recorded.
+        cache.with_stats_recorder(R::default())
+    }
+}
+
+impl<'de, K, V, P, R, H> Visitor<'de> for CacheVisitor<K, V, P, R, H>
+where
+    K: Deserialize<'de> + Eq + Hash,
+    V: Deserialize<'de>,
+    P: EvictionPolicy + Default,
+    R: StatsRecorder + Default,
+    H: BuildHasher + Default,
+{
+    type Value = LruCache<K, V, P, R, H>;
+
+    fn expecting(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("an LRU cache")
+    }
+
+    fn visit_seq<A>(self, mut seq: A) -> Result<Self::Value, A::Error>
+    where
+        A: SeqAccess<'de>,
+    {
+        let capacity = seq
+            .next_element()?
+            .ok_or_else(|| de::Error::invalid_length(0, &self))?;
+        let entries = seq
+            .next_element()?
+            .ok_or_else(|| de::Error::invalid_length(1, &self))?;
+
+        Ok(Self::build(capacity, entries))
+    }
+
+    fn visit_map<A>(self, mut map: A) -> Result<Self::Value, A::Error>
+    where
+        A: MapAccess<'de>,
+    {
+        let mut capacity = None;
+        let mut entries = None;
+
+        while let Some(field) = map.next_key()? {
+            match field {
+                Field::Capacity if capacity.is_some() => {
+                    return Err(de::Error::duplicate_field("capacity"));
+                }
+                Field::Capacity => capacity = Some(map.next_value()?),
+                Field::Entries if entries.is_some() => {
+                    return Err(de::Error::duplicate_field("entries"));
+                }
+                Field::Entries => entries = Some(map.next_value()?),
+            }
+        }
+
+        let capacity = capacity.ok_or_else(|| de::Error::missing_field("capacity"))?;
+        let entries = entries.ok_or_else(|| de::Error::missing_field("entries"))?;
+
+        Ok(Self::build(capacity, entries))
+    }
+}
+
+enum Field {
+    Capacity,
+    Entries,
+}
+
+impl<'de> Deserialize<'de> for Field {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        deserializer.deserialize_identifier(FieldVisitor)
+    }
+}
+
+struct FieldVisitor;
+
+impl<'de> Visitor<'de> for FieldVisitor {
+    type Value = Field;
+
+    fn expecting(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("`capacity` or `entries`")
+    }
+
+    fn visit_str<E>(self, value: &str) -> Result<Field, E>
+    where
+        E: de::Error,
+    {
+        match value {
+            "capacity" => Ok(Field::Capacity),
+            "entries" => Ok(Field::Entries),
+            _ => Err(de::Error::unknown_field(value, FIELDS)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        string::{String, ToString},
+        time::Duration,
+    };
+
+    use crate::{policy::Fifo, ManualClock, StatsCounter};
+
+    use super::*;
+
+    fn warm_cache() -> LruCache<String, u32> {
+        let mut cache = LruCache::new(4);
+
+        for (i, k) in ["a", "b", "c"].into_iter().enumerate() {
+            cache.insert(k.to_string(), i as u32);
+        }
+
+        cache.get("a");
+        cache
+    }
+
+    fn entries<K, V, P, R, H>(cache: &LruCache<K, V, P, R, H>) -> Vec<(K, V)>
+    where
+        K: Eq + Hash + Clone,
+        V: Clone,
+        P: EvictionPolicy,
+        R: StatsRecorder,
+        H: BuildHasher,
+    {
+        cache.iter().map(|(k, v)| (k.clone(), v.clone())).collect()
+    }
+
+    #[test]
+    fn test_json_round_trip() {
+        let cache = warm_cache();
+        let json = serde_json::to_string(&cache).unwrap();
+        assert_eq!(
+            json,
+            r#"{"capacity":4,"entries":[["b",1],["c",2],["a",0]]}"#
+        );
+
+        let restored: LruCache<String, u32> = serde_json::from_str(&json).unwrap();
+        assert_eq!(restored.capacity(), 4);
+        assert_eq!(entries(&restored), entries(&cache));
+    }
+
+    #[test]
+    fn test_bincode_round_trip() {
+        let cache = warm_cache();
+        let bytes = bincode::serialize(&cache).unwrap();
+
+        let mut restored: LruCache<String, u32, Fifo, StatsCounter> =
+            bincode::deserialize(&bytes).unwrap();
+        assert_eq!(restored.capacity(), 4);
+        assert_eq!(entries(&restored), entries(&cache));
+
+        restored.insert("d".to_string(), 3);
+        restored.insert("e".to_string(), 4);
+        assert!(!restored.contains_key("b"));
+        assert_eq!(restored.stats().inserts, 2);
+    }
+
+    #[test]
+    fn test_skips_expired_entries() {
+        let clock = ManualClock::new();
+        let mut cache = LruCache::new(2);
+        cache.set_clock(clock.clone());
+        cache.insert_with_ttl(1, 1, Duration::from_secs(1));
+        cache.insert(2, 2);
+        clock.advance(Duration::from_secs(1));
+
+        let json = serde_json::to_string(&cache).unwrap();
+        assert_eq!(json, r#"{"capacity":2,"entries":[[2,2]]}"#);
+    }
+}
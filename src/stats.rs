@@ -0,0 +1,116 @@
+use crate::EvictionReason;
+
+/// Receives the events of an [`LruCache`](crate::LruCache) worth counting.
+///
+/// Every method does nothing by default. The cache records through
+/// [`NoStats`] unless told otherwise, which compiles down to nothing.
+pub trait StatsRecorder {
+    /// A lookup through `get`, `get_mut` or `entry` found a live entry.
+    fn record_hit(&mut self) {}
+
+    /// A lookup through `get`, `get_mut` or `entry` found nothing.
+    fn record_miss(&mut self) {}
+
+    /// A new entry was stored.
+    fn record_insert(&mut self) {}
+
+    /// An entry, or a pair too heavy to store, left the cache.
+    fn record_eviction(&mut self, _reason: EvictionReason) {}
+}
+
+/// The default recorder, which records nothing.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct NoStats;
+
+impl StatsRecorder for NoStats {}
+
+/// A recorder keeping a count of every event, read through
+/// [`LruCache::stats`](crate::LruCache::stats).
+#[derive(Clone, Copy, Debug, Default)]
+pub struct StatsCounter {
+    hits: u64,
+    misses: u64,
+    inserts: u64,
+    updates: u64,
+    evictions: u64,
+    expirations: u64,
+    removals: u64,
+}
+
+impl StatsCounter {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub(crate) fn snapshot(&self, len: usize, weight: usize) -> CacheStats {
+        CacheStats {
+            hits: self.hits,
+            misses: self.misses,
+            inserts: self.inserts,
+            updates: self.updates,
+            evictions: self.evictions,
+            expirations: self.expirations,
+            removals: self.removals,
+            len,
+            weight,
+        }
+    }
+}
+
+impl StatsRecorder for StatsCounter {
+    fn record_hit(&mut self) {
+        self.hits += 1;
+    }
+
+    fn record_miss(&mut self) {
+        self.misses += 1;
+    }
+
+    fn record_insert(&mut self) {
+        self.inserts += 1;
+    }
+
+    fn record_eviction(&mut self, reason: EvictionReason) {
+        let count = match reason {
+            EvictionReason::Capacity => &mut self.evictions,
+            EvictionReason::Removed => &mut self.removals,
+            EvictionReason::Replaced => &mut self.updates,
+            EvictionReason::Expired => &mut self.expirations,
+        };
+
+        *count += 1;
+    }
+}
+
+/// A snapshot of the counts kept by a [`StatsCounter`], along with the size of
+/// the cache when it was taken.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub struct CacheStats {
+    pub hits: u64,
+    pub misses: u64,
+    /// New entries stored.
+    pub inserts: u64,
+    /// Values replaced by an insert for a key already present.
+    pub updates: u64,
+    /// Entries evicted to make room, including pairs too heavy to be stored.
+    pub evictions: u64,
+    /// Entries dropped because they outlived their time-to-live.
+    pub expirations: u64,
+    /// Entries removed explicitly.
+    pub removals: u64,
+    pub len: usize,
+    pub weight: usize,
+}
+
+impl CacheStats {
+    /// Returns the share of lookups that were hits, or 0 if there were none.
+    pub fn hit_rate(&self) -> f64 {
+        let lookups = self.hits + self.misses;
+
+        if lookups == 0 {
+            return 0.0;
+        }
+
+        self.hits as f64 / lookups as f64
+    }
+}